@@ -0,0 +1,17 @@
+extern crate cli_clipboard;
+
+#[cfg(target_os = "linux")]
+fn main() {
+    use cli_clipboard::{ClipboardContext, ClipboardProvider};
+
+    let mut ctx = ClipboardContext::new().unwrap();
+
+    for format in ctx.available_formats().unwrap() {
+        println!("{}", format);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {
+    println!("Listing the available formats is only supported under linux!");
+}
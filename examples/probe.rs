@@ -0,0 +1,11 @@
+extern crate cli_clipboard;
+
+#[cfg(target_os = "linux")]
+fn main() {
+    print!("{}", cli_clipboard::linux_clipboard::probe());
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {
+    println!("Backend detection is only needed under linux!");
+}
@@ -0,0 +1,18 @@
+extern crate cli_clipboard;
+
+#[cfg(target_os = "linux")]
+fn main() {
+    use cli_clipboard::{ClipboardContext, ClipboardProvider};
+
+    let ctx = ClipboardContext::new().unwrap();
+    ctx.watch_with(|event| {
+        println!("{:?}: {}", event.kind, event.mime_types.join(", "));
+        true
+    })
+    .unwrap();
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {
+    println!("Watching the clipboard is only supported under linux!");
+}
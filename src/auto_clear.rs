@@ -0,0 +1,146 @@
+//! Clearing the clipboard after a timeout, e.g. for copied passwords.
+
+use crate::common::ClipboardProvider;
+use crate::error::{ClipboardError, Result};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use zeroize::Zeroizing;
+
+/// Copies text and clears the clipboard again once a timeout has passed,
+/// like `pass -c`.
+///
+/// The clipboard is only cleared if it still holds the copied text, see
+/// `ClipboardProvider::clear_if_unchanged`, so that anything copied in
+/// the meantime is left alone.
+///
+/// The provider waits on a background thread. On Wayland and X11 the
+/// contents are only available while the copying process is running, so
+/// it must not exit before the timeout. Dropping an `AutoClear` leaves
+/// the thread running, use `cancel` to keep the contents instead.
+///
+/// # Example
+///
+/// ```noop
+/// use cli_clipboard::auto_clear::AutoClear;
+/// use cli_clipboard::{ClipboardContext, ClipboardProvider};
+/// use std::time::Duration;
+///
+/// let ctx = ClipboardContext::new().unwrap();
+/// let copy = AutoClear::sensitive(ctx, "hunter2".to_owned(), Duration::from_secs(45)).unwrap();
+/// // serve the password for 45 seconds, unless something else is copied first
+/// copy.wait().unwrap();
+/// ```
+pub struct AutoClear<P> {
+    cancelled: Arc<(Mutex<bool>, Condvar)>,
+    thread: JoinHandle<(P, Result<bool>)>,
+}
+
+impl<P> AutoClear<P>
+where
+    P: ClipboardProvider + Send + 'static,
+{
+    /// Copies `data` with `provider` and clears it after `timeout`.
+    pub fn new(provider: P, data: String, timeout: Duration) -> Result<AutoClear<P>> {
+        AutoClear::start(provider, data, timeout, false)
+    }
+
+    /// Like `new`, but copies `data` with `set_sensitive_contents` so
+    /// that clipboard managers don't record it.
+    pub fn sensitive(provider: P, data: String, timeout: Duration) -> Result<AutoClear<P>> {
+        AutoClear::start(provider, data, timeout, true)
+    }
+
+    fn start(mut provider: P, data: String, timeout: Duration, sensitive: bool) -> Result<Self> {
+        let data = Zeroizing::new(data);
+        if sensitive {
+            provider.set_sensitive_contents(String::clone(&data))?;
+        } else {
+            provider.set_contents(String::clone(&data))?;
+        }
+
+        let cancelled = Arc::new((Mutex::new(false), Condvar::new()));
+        let state = Arc::clone(&cancelled);
+        let thread = thread::spawn(move || {
+            let deadline = Instant::now() + timeout;
+            let (lock, condvar) = &*state;
+            let mut cancelled = lock.lock().unwrap_or_else(|e| e.into_inner());
+            while !*cancelled {
+                let now = Instant::now();
+                if now >= deadline {
+                    break;
+                }
+                cancelled = condvar
+                    .wait_timeout(cancelled, deadline - now)
+                    .unwrap_or_else(|e| e.into_inner())
+                    .0;
+            }
+
+            let result = if *cancelled {
+                Ok(false)
+            } else {
+                provider.clear_if_unchanged(&data)
+            };
+            drop(cancelled);
+            (provider, result)
+        });
+
+        Ok(AutoClear { cancelled, thread })
+    }
+
+    /// Cancels clearing the clipboard and returns the provider, which on
+    /// X11 keeps serving the contents for as long as it is alive. Has no
+    /// effect on the clipboard if the timeout has already passed.
+    pub fn cancel(self) -> Result<P> {
+        {
+            let (lock, condvar) = &*self.cancelled;
+            *lock.lock().unwrap_or_else(|e| e.into_inner()) = true;
+            condvar.notify_all();
+        }
+        self.join().map(|(provider, _)| provider)
+    }
+
+    /// Blocks until the timeout has passed and returns whether the
+    /// clipboard was cleared, i.e. false if something else was copied
+    /// in the meantime.
+    pub fn wait(self) -> Result<bool> {
+        self.join()?.1
+    }
+
+    fn join(self) -> Result<(P, Result<bool>)> {
+        self.thread
+            .join()
+            .map_err(|_| ClipboardError::Backend("the auto-clear thread panicked".into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{ClipboardContent, ClipboardKind};
+    use crate::memory_clipboard::{MemoryClipboard, MemoryClipboardContext};
+
+    #[test]
+    fn clears_only_own_contents() {
+        let clipboard = MemoryClipboard::new();
+        let ctx = || MemoryClipboardContext::with_clipboard(clipboard.clone());
+        let timeout = Duration::from_millis(10);
+
+        let copy = AutoClear::sensitive(ctx(), "hunter2".to_owned(), timeout).unwrap();
+        assert_eq!(ctx().get_contents().unwrap(), "hunter2");
+        assert!(copy.wait().unwrap());
+        assert_eq!(ctx().get_contents_opt().unwrap(), None);
+
+        let copy = AutoClear::new(ctx(), "hunter2".to_owned(), timeout).unwrap();
+        let other = vec![ClipboardContent::Text("copied since".to_owned())];
+        clipboard.set(ClipboardKind::Clipboard, other);
+        assert!(!copy.wait().unwrap());
+        assert_eq!(ctx().get_contents().unwrap(), "copied since");
+
+        let copy = AutoClear::new(ctx(), "hunter2".to_owned(), Duration::from_secs(60)).unwrap();
+        let started = Instant::now();
+        copy.cancel().unwrap();
+        assert!(started.elapsed() < Duration::from_secs(60));
+        assert_eq!(ctx().get_contents().unwrap(), "hunter2");
+    }
+}
@@ -0,0 +1,253 @@
+use crate::common::*;
+use crate::error::{ClipboardError, Result};
+use std::env;
+use std::ffi::OsStr;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+// Messages clipboard tools print when there is nothing to paste.
+const EMPTY_MESSAGES: [&str; 3] = ["Nothing is copied", "No selection", "no buffer"];
+
+// xclip prints `Error: target UTF8_STRING not available` instead.
+const XCLIP_EMPTY_PREFIX: &str = "Error: target ";
+const XCLIP_EMPTY_SUFFIX: &str = " not available";
+
+/// The commands a `CommandClipboardContext` runs, each given as the
+/// program followed by its arguments.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommandTemplates {
+    /// Reads the text to copy from stdin
+    pub copy: Vec<String>,
+    /// Writes the clipboard text to stdout
+    pub paste: Vec<String>,
+    /// Clears the clipboard. If None, clearing is unsupported, as
+    /// copying an empty string would not leave the clipboard empty.
+    pub clear: Option<Vec<String>>,
+}
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|&arg| arg.to_owned()).collect()
+}
+
+impl CommandTemplates {
+    /// `wl-copy` and `wl-paste` from wl-clipboard
+    pub fn wl_clipboard() -> CommandTemplates {
+        CommandTemplates {
+            copy: args(&["wl-copy"]),
+            paste: args(&["wl-paste", "--no-newline"]),
+            clear: Some(args(&["wl-copy", "--clear"])),
+        }
+    }
+
+    /// `xclip`, using the CLIPBOARD selection
+    pub fn xclip() -> CommandTemplates {
+        CommandTemplates {
+            copy: args(&["xclip", "-selection", "clipboard", "-in"]),
+            paste: args(&["xclip", "-selection", "clipboard", "-out"]),
+            clear: None,
+        }
+    }
+
+    /// `xsel`, using the CLIPBOARD selection
+    pub fn xsel() -> CommandTemplates {
+        CommandTemplates {
+            copy: args(&["xsel", "--clipboard", "--input"]),
+            paste: args(&["xsel", "--clipboard", "--output"]),
+            clear: Some(args(&["xsel", "--clipboard", "--clear"])),
+        }
+    }
+
+    /// Picks the templates for the first tool found on `PATH`.
+    ///
+    /// wl-clipboard is preferred in Wayland sessions, xclip and then xsel
+    /// otherwise.
+    pub fn detect() -> Option<CommandTemplates> {
+        let mut candidates = vec![
+            CommandTemplates::xclip(),
+            CommandTemplates::xsel(),
+            CommandTemplates::wl_clipboard(),
+        ];
+        if env::var_os("WAYLAND_DISPLAY").is_some() {
+            candidates.rotate_right(1);
+        }
+        candidates
+            .into_iter()
+            .find(|templates| on_path(&templates.copy[0]) && on_path(&templates.paste[0]))
+    }
+}
+
+/// Interface to the clipboard through external tools such as `xclip`,
+/// `xsel` or `wl-copy` and `wl-paste`.
+///
+/// This is a last resort for systems where the linked-in Wayland and X11
+/// providers fail, e.g. compositors without the data-control protocol,
+/// while the user's own clipboard tools work. Only plain text is
+/// supported.
+///
+/// # Example
+///
+/// ```noop
+/// use cli_clipboard::ClipboardProvider;
+/// use cli_clipboard::command_clipboard::{CommandClipboardContext, CommandTemplates};
+///
+/// let mut clipboard = CommandClipboardContext::with_templates(CommandTemplates::xsel());
+/// clipboard.set_contents("foo bar baz".to_string()).unwrap();
+/// assert_eq!(clipboard.get_contents().unwrap(), "foo bar baz");
+/// ```
+pub struct CommandClipboardContext {
+    templates: CommandTemplates,
+}
+
+impl CommandClipboardContext {
+    /// Creates a context that runs the given commands.
+    pub fn with_templates(templates: CommandTemplates) -> CommandClipboardContext {
+        CommandClipboardContext { templates }
+    }
+
+    /// The commands this context runs.
+    pub fn templates(&self) -> &CommandTemplates {
+        &self.templates
+    }
+}
+
+impl ClipboardProvider for CommandClipboardContext {
+    /// Uses the first supported tool found on `PATH`, see
+    /// `CommandTemplates::detect`.
+    fn new() -> Result<CommandClipboardContext> {
+        CommandTemplates::detect()
+            .map(CommandClipboardContext::with_templates)
+            .ok_or_else(|| {
+                ClipboardError::NoDisplayServer(
+                    "none of wl-copy, xclip or xsel was found on PATH".into(),
+                )
+            })
+    }
+
+    fn get_contents(&mut self) -> Result<String> {
+        Ok(String::from_utf8(self.get_bytes("text/plain")?)?)
+    }
+
+    /// Returns the raw output of the paste command. Only plain text is
+    /// supported.
+    fn get_bytes(&mut self, mime_type: &str) -> Result<Vec<u8>> {
+        if !is_plain_text(mime_type) {
+            return Err(ClipboardError::Unsupported(format!(
+                "MIME type {}",
+                mime_type
+            )));
+        }
+        run(&self.templates.paste, None)
+    }
+
+    fn set_contents(&mut self, data: String) -> Result<()> {
+        run(&self.templates.copy, Some(data.as_bytes())).map(drop)
+    }
+
+    fn clear(&mut self) -> Result<()> {
+        match &self.templates.clear {
+            Some(command) => run(command, None).map(drop),
+            None => Err(ClipboardError::Unsupported(format!(
+                "clearing the clipboard with {}",
+                self.templates.copy[0]
+            ))),
+        }
+    }
+}
+
+fn on_path(program: &str) -> bool {
+    env::var_os("PATH")
+        .map(|paths| env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+        .unwrap_or(false)
+}
+
+/// Runs `command`, feeding it `input`, and returns its output.
+///
+/// Copying tools like `xclip` and `wl-copy` fork a child that serves the
+/// clipboard and keeps running, so when there is `input` the output is
+/// not captured, as reading it would wait for that child to exit.
+///
+/// Returns `ClipboardError::Empty` if the command fails with one of the
+/// messages clipboard tools print when there is nothing to paste.
+pub(crate) fn run<S: AsRef<OsStr>>(command: &[S], input: Option<&[u8]>) -> Result<Vec<u8>> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| ClipboardError::Unsupported("running an empty command".to_owned()))?;
+    let mut command = Command::new(program);
+    command.args(args);
+    let spawn_error = |e| ClipboardError::NoDisplayServer(Box::new(e));
+    let program = Path::new(program.as_ref()).display();
+
+    if let Some(input) = input {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(spawn_error)?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(input)?;
+        }
+        let status = child.wait()?;
+        return if status.success() {
+            Ok(Vec::new())
+        } else {
+            Err(ClipboardError::Backend(
+                format!("{} failed with {}", program, status).into(),
+            ))
+        };
+    }
+
+    let output = command.stdin(Stdio::null()).output().map_err(spawn_error)?;
+    if output.status.success() {
+        return Ok(output.stdout);
+    }
+
+    let message = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+    let xclip_empty =
+        message.starts_with(XCLIP_EMPTY_PREFIX) && message.ends_with(XCLIP_EMPTY_SUFFIX);
+    if xclip_empty || EMPTY_MESSAGES.iter().any(|empty| message.contains(empty)) {
+        Err(ClipboardError::Empty)
+    } else {
+        Err(ClipboardError::Backend(
+            format!("{}: {}", program, message).into(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_commands() {
+        assert_eq!(run(&["echo", "foo"], None).unwrap(), b"foo\n");
+        assert!(run(&["cat"], Some(b"foo bar baz")).is_ok());
+        assert!(matches!(
+            run(
+                &["sh", "-c", "cat >/dev/null; exit 1"],
+                Some(b"foo bar baz")
+            ),
+            Err(ClipboardError::Backend(_))
+        ));
+        assert!(matches!(
+            run(&["sh", "-c", "echo 'Nothing is copied' >&2; exit 1"], None),
+            Err(ClipboardError::Empty)
+        ));
+        assert!(matches!(
+            run(
+                &[
+                    "sh",
+                    "-c",
+                    "echo 'Error: target STRING not available' >&2; exit 1"
+                ],
+                None
+            ),
+            Err(ClipboardError::Empty)
+        ));
+        assert!(matches!(
+            run(&["sh", "-c", "echo oops >&2; exit 1"], None),
+            Err(ClipboardError::Backend(_))
+        ));
+    }
+}
@@ -14,18 +14,374 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
-use anyhow::Result;
+use crate::error::{ClipboardError, Result};
+use std::io::{Read, Write};
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+const TEXT_MIME_TYPE: &str = "text/plain;charset=utf-8";
+const HTML_MIME_TYPE: &str = "text/html";
+const RTF_MIME_TYPE: &str = "text/rtf";
+const PNG_MIME_TYPE: &str = "image/png";
+const URI_LIST_MIME_TYPE: &str = "text/uri-list";
+
+/// MIME type password managers offer to ask clipboard managers not to
+/// record a copy, with the value `secret`
+pub(crate) const PASSWORD_MANAGER_HINT: &str = "x-kde-passwordManagerHint";
+
+/// The value offered under `PASSWORD_MANAGER_HINT`
+pub(crate) const PASSWORD_MANAGER_HINT_VALUE: &[u8] = b"secret";
+
+/// The representation marking a copy as a secret
+pub(crate) fn password_manager_hint() -> ClipboardContent {
+    ClipboardContent::Other {
+        mime_type: PASSWORD_MANAGER_HINT.to_owned(),
+        data: PASSWORD_MANAGER_HINT_VALUE.to_vec(),
+    }
+}
+
+/// Error for copying an empty list of representations
+pub(crate) fn no_representations() -> ClipboardError {
+    ClipboardError::Unsupported("copying an empty list of representations".to_owned())
+}
+
+/// Returns whether `mime_type` names UTF-8 plain text
+pub(crate) fn is_plain_text(mime_type: &str) -> bool {
+    matches!(
+        mime_type,
+        "text/plain" | "text/plain;charset=utf-8" | "UTF8_STRING"
+    )
+}
+
+/// The selection a clipboard context operates on
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ClipboardKind {
+    /// The regular clipboard, used by explicit copy and paste
+    Clipboard,
+    /// The primary selection, set by selecting text and pasted with the
+    /// middle mouse button
+    Primary,
+    /// The X11 SECONDARY selection, used by some editors for swapping
+    /// text. Not available on Wayland.
+    Secondary,
+    /// The regular clipboard and the primary selection together. Copying
+    /// and clearing affect both, pasting reads the regular clipboard and
+    /// falls back to the primary selection if the clipboard is empty.
+    Both,
+}
+
+/// Platform-agnostic clipboard contents
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ClipboardContent {
+    /// UTF-8 plain text
+    Text(String),
+    /// HTML markup, offered as `text/html`
+    Html(String),
+    /// Rich Text Format, offered as `text/rtf`
+    Rtf(String),
+    /// PNG encoded image, offered as `image/png`
+    Png(Vec<u8>),
+    /// List of URIs (usually `file://` URIs), offered as `text/uri-list`
+    UriList(Vec<String>),
+    /// Raw bytes offered under any other MIME type
+    Other { mime_type: String, data: Vec<u8> },
+}
+
+impl ClipboardContent {
+    /// Build contents from raw clipboard bytes of the given MIME type
+    ///
+    /// Well-known MIME types are decoded into their dedicated variants,
+    /// anything else ends up in `ClipboardContent::Other`. Returns Err if
+    /// a textual MIME type holds invalid UTF-8.
+    pub fn from_bytes(mime_type: &str, data: Vec<u8>) -> Result<ClipboardContent> {
+        Ok(match mime_type {
+            mime_type if is_plain_text(mime_type) => {
+                ClipboardContent::Text(String::from_utf8(data)?)
+            }
+            HTML_MIME_TYPE => ClipboardContent::Html(String::from_utf8(data)?),
+            RTF_MIME_TYPE | "application/rtf" => ClipboardContent::Rtf(String::from_utf8(data)?),
+            PNG_MIME_TYPE => ClipboardContent::Png(data),
+            URI_LIST_MIME_TYPE => ClipboardContent::UriList(
+                String::from_utf8(data)?
+                    .lines()
+                    .map(str::trim_end)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(str::to_owned)
+                    .collect(),
+            ),
+            _ => ClipboardContent::Other {
+                mime_type: mime_type.to_owned(),
+                data,
+            },
+        })
+    }
+
+    /// The MIME type these contents are offered under
+    pub fn mime_type(&self) -> &str {
+        match self {
+            ClipboardContent::Text(_) => TEXT_MIME_TYPE,
+            ClipboardContent::Html(_) => HTML_MIME_TYPE,
+            ClipboardContent::Rtf(_) => RTF_MIME_TYPE,
+            ClipboardContent::Png(_) => PNG_MIME_TYPE,
+            ClipboardContent::UriList(_) => URI_LIST_MIME_TYPE,
+            ClipboardContent::Other { mime_type, .. } => mime_type,
+        }
+    }
+
+    /// The raw bytes that get placed on the clipboard
+    pub fn into_bytes(self) -> Vec<u8> {
+        match self {
+            ClipboardContent::Text(text)
+            | ClipboardContent::Html(text)
+            | ClipboardContent::Rtf(text) => text.into_bytes(),
+            ClipboardContent::Png(data) | ClipboardContent::Other { data, .. } => data,
+            ClipboardContent::UriList(uris) => uris
+                .iter()
+                .flat_map(|uri| vec![uri.as_str(), "\r\n"])
+                .collect::<String>()
+                .into_bytes(),
+        }
+    }
+}
+
+/// A change of the clipboard or primary selection, as reported by a
+/// clipboard watcher
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClipboardEvent {
+    /// The selection that changed
+    pub kind: ClipboardKind,
+    /// The formats offered by the new owner, sorted by name: MIME types
+    /// and, from X11 clients, target names such as `UTF8_STRING`. Empty
+    /// if the selection was cleared.
+    pub mime_types: Vec<String>,
+}
 
 /// Trait for clipboard access
-pub trait ClipboardProvider: Sized {
+///
+/// The trait is object safe, so providers can be chosen at runtime and
+/// stored as `Box<dyn ClipboardProvider>`, see `boxed_context`.
+pub trait ClipboardProvider {
     /// Create a context with which to access the clipboard
-    fn new() -> Result<Self>;
+    fn new() -> Result<Self>
+    where
+        Self: Sized;
     /// Method to get the clipboard contents as a String
+    ///
+    /// Returns `ClipboardError::Empty` if nothing has been copied, as
+    /// opposed to an empty string having been copied.
     fn get_contents(&mut self) -> Result<String>;
     /// Method to set the clipboard contents as a String
     fn set_contents(&mut self, content: String) -> Result<()>;
-    // TODO: come up with some platform-agnostic API for richer types
-    // than just strings (c.f. issue #31)
     /// Method to clear the clipboard
     fn clear(&mut self) -> Result<()>;
+    /// Method to get the clipboard contents as a String, or None if
+    /// nothing has been copied
+    fn get_contents_opt(&mut self) -> Result<Option<String>> {
+        match self.get_contents() {
+            Ok(contents) => Ok(Some(contents)),
+            Err(ClipboardError::Empty) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+    /// Method to set the clipboard contents as a String and keep them
+    /// available for at least `duration`
+    ///
+    /// Providers whose contents outlive the process (macOS, Windows)
+    /// return as soon as the contents are set.
+    fn set_contents_for_duration(&mut self, content: String, _duration: Duration) -> Result<()> {
+        self.set_contents(content)
+    }
+    /// Method to clear the clipboard, but only if it still holds `data`
+    /// as copied by this context, returning whether it was cleared
+    ///
+    /// Contents copied by another application since are left alone. By
+    /// default the current text is compared with `data`, providers that
+    /// know whether they still own the clipboard check that instead.
+    fn clear_if_unchanged(&mut self, data: &str) -> Result<bool> {
+        // the contents may well be the secret, don't leave a copy behind
+        match self.get_bytes(TEXT_MIME_TYPE).map(Zeroizing::new) {
+            Ok(contents) if contents.as_slice() == data.as_bytes() => self.clear().map(|()| true),
+            Ok(_) | Err(ClipboardError::Empty) | Err(ClipboardError::FormatUnavailable(_)) => {
+                Ok(false)
+            }
+            Err(e) => Err(e),
+        }
+    }
+    /// Method to get the clipboard contents offered under `mime_type`
+    ///
+    /// Providers that only support plain text return Err for any other
+    /// MIME type.
+    fn get_content(&mut self, mime_type: &str) -> Result<ClipboardContent> {
+        if is_plain_text(mime_type) {
+            Ok(ClipboardContent::Text(self.get_contents()?))
+        } else {
+            Err(ClipboardError::Unsupported(format!(
+                "MIME type {}",
+                mime_type
+            )))
+        }
+    }
+    /// Method to get the raw bytes offered under `mime_type`, without
+    /// requiring text to be valid UTF-8
+    fn get_bytes(&mut self, mime_type: &str) -> Result<Vec<u8>> {
+        Ok(self.get_content(mime_type)?.into_bytes())
+    }
+    /// Method to set the clipboard contents to raw bytes offered under
+    /// `mime_type`
+    ///
+    /// Providers that decode the data first return Err if text is not
+    /// valid UTF-8.
+    fn set_bytes(&mut self, mime_type: &str, data: Vec<u8>) -> Result<()> {
+        self.set_content(ClipboardContent::from_bytes(mime_type, data)?)
+    }
+    /// Method to write the raw bytes offered under `mime_type` to
+    /// `writer`, returning the number of bytes written
+    ///
+    /// Providers that can stream the contents write them as they arrive
+    /// instead of holding them in memory.
+    fn paste_to_writer(&mut self, mime_type: &str, writer: &mut dyn Write) -> Result<u64> {
+        let data = self.get_bytes(mime_type)?;
+        writer.write_all(&data)?;
+        Ok(data.len() as u64)
+    }
+    /// Method to copy everything read from `reader` under `mime_type`
+    ///
+    /// Providers that serve paste requests themselves, like the Wayland
+    /// and X11 ones, hold the data in memory to answer every request.
+    fn copy_from_reader(&mut self, mime_type: &str, reader: &mut dyn Read) -> Result<()> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        self.set_bytes(mime_type, data)
+    }
+    /// Method to get the clipboard contents as a String, replacing
+    /// invalid UTF-8 sequences with U+FFFD
+    fn get_contents_lossy(&mut self) -> Result<String> {
+        let data = self.get_bytes(TEXT_MIME_TYPE)?;
+        Ok(String::from_utf8_lossy(&data).into_owned())
+    }
+    /// Method to set the clipboard contents
+    ///
+    /// Providers that only support plain text return Err for anything
+    /// but `ClipboardContent::Text`.
+    fn set_content(&mut self, content: ClipboardContent) -> Result<()> {
+        match content {
+            ClipboardContent::Text(text) => self.set_contents(text),
+            content => Err(ClipboardError::Unsupported(format!(
+                "MIME type {}",
+                content.mime_type()
+            ))),
+        }
+    }
+    /// Method to offer several representations of the same clipboard
+    /// contents at once, e.g. HTML along with plain text
+    ///
+    /// Pasting applications pick whichever representation suits them
+    /// best. Providers that only support plain text set the
+    /// `ClipboardContent::Text` representation, if there is one.
+    fn set_content_multi(&mut self, contents: Vec<ClipboardContent>) -> Result<()> {
+        let mut contents = contents.into_iter();
+        let first = contents.next().ok_or_else(no_representations)?;
+        let content = match first {
+            ClipboardContent::Text(_) => first,
+            first => contents
+                .find(|content| matches!(content, ClipboardContent::Text(_)))
+                .unwrap_or(first),
+        };
+        self.set_content(content)
+    }
+    /// Method to copy a secret, such as a password, asking clipboard
+    /// managers not to record it
+    ///
+    /// Besides the text, providers that support several representations
+    /// offer the `x-kde-passwordManagerHint` MIME type with the value
+    /// `secret`, which clipboard managers such as Klipper, GPaste and
+    /// CopyQ leave out of their history. Other providers copy the text
+    /// as usual.
+    fn set_sensitive_contents(&mut self, data: String) -> Result<()> {
+        self.set_content_multi(vec![ClipboardContent::Text(data), password_manager_hint()])
+    }
+    /// Method to list the formats offered by the current clipboard owner
+    ///
+    /// On Wayland and X11 these are MIME type (or X11 target) names.
+    /// Providers that cannot list formats return Err.
+    fn available_formats(&mut self) -> Result<Vec<String>> {
+        Err(ClipboardError::Unsupported(
+            "listing clipboard formats".to_owned(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_round_trip() {
+        let contents = vec![
+            ClipboardContent::Text("some text".to_owned()),
+            ClipboardContent::Html("<b>bold</b>".to_owned()),
+            ClipboardContent::Rtf("{\\rtf1 plain}".to_owned()),
+            ClipboardContent::Png(vec![0x89, b'P', b'N', b'G']),
+            ClipboardContent::UriList(vec![
+                "file:///etc/hosts".to_owned(),
+                "file:///etc/passwd".to_owned(),
+            ]),
+            ClipboardContent::Other {
+                mime_type: "application/x-custom".to_owned(),
+                data: vec![0, 1, 2],
+            },
+        ];
+
+        for content in contents {
+            let mime_type = content.mime_type().to_owned();
+            let decoded = ClipboardContent::from_bytes(&mime_type, content.clone().into_bytes())
+                .expect("couldn't decode clipboard content");
+            assert_eq!(decoded, content);
+        }
+    }
+
+    struct EmptyClipboard;
+
+    impl ClipboardProvider for EmptyClipboard {
+        fn new() -> Result<EmptyClipboard> {
+            Ok(EmptyClipboard)
+        }
+        fn get_contents(&mut self) -> Result<String> {
+            Err(ClipboardError::Empty)
+        }
+        fn set_contents(&mut self, _: String) -> Result<()> {
+            Ok(())
+        }
+        fn clear(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn empty_clipboard_is_none() {
+        let mut clipboard = EmptyClipboard::new().unwrap();
+        assert!(matches!(
+            clipboard.get_contents(),
+            Err(ClipboardError::Empty)
+        ));
+        assert_eq!(clipboard.get_contents_opt().unwrap(), None);
+    }
+
+    #[test]
+    fn usable_as_trait_object() {
+        let mut providers: Vec<Box<dyn ClipboardProvider>> =
+            vec![Box::new(EmptyClipboard::new().unwrap())];
+        for provider in &mut providers {
+            assert_eq!(provider.get_contents_opt().unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn uri_list_skips_comments() {
+        let data = b"# copied from a file manager\r\nfile:///tmp/a\r\n\r\nfile:///tmp/b\n".to_vec();
+        assert_eq!(
+            ClipboardContent::from_bytes("text/uri-list", data).unwrap(),
+            ClipboardContent::UriList(vec!["file:///tmp/a".to_owned(), "file:///tmp/b".to_owned()])
+        );
+    }
 }
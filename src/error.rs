@@ -0,0 +1,134 @@
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::string::FromUtf8Error;
+
+/// Result type returned by every clipboard operation
+pub type Result<T> = std::result::Result<T, ClipboardError>;
+
+/// Errors that can occur while accessing the clipboard
+///
+/// Variants that wrap another error include its message in their own,
+/// so they don't return it from `source` as well.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ClipboardError {
+    /// Nothing has been copied to the clipboard
+    Empty,
+    /// No display server (Wayland compositor or X server) could be reached
+    NoDisplayServer(Box<dyn StdError + Send + Sync>),
+    /// The display server lacks a protocol or extension the provider needs
+    UnsupportedProtocol(String),
+    /// The provider does not support the requested operation
+    Unsupported(String),
+    /// The clipboard does not offer contents in the requested format
+    FormatUnavailable(String),
+    /// The clipboard contents are not valid UTF-8
+    InvalidUtf8(FromUtf8Error),
+    /// The selection owner did not answer in time
+    Timeout,
+    /// Reading or writing the clipboard data failed
+    Io(io::Error),
+    /// Any other error reported by the platform clipboard
+    Backend(Box<dyn StdError + Send + Sync>),
+    /// Every backend that was tried failed, listed with the reason for
+    /// each in the order they were tried
+    NoBackendAvailable(Vec<(String, ClipboardError)>),
+    /// Reading or writing the clipboard history file at the path failed
+    HistoryFile(PathBuf, io::Error),
+}
+
+impl fmt::Display for ClipboardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::ClipboardError::*;
+        match self {
+            Empty => write!(f, "the clipboard is empty"),
+            NoDisplayServer(e) => write!(f, "couldn't connect to the display server: {}", e),
+            UnsupportedProtocol(protocol) => {
+                write!(f, "the display server does not support {}", protocol)
+            }
+            Unsupported(operation) => write!(f, "{} is not supported", operation),
+            FormatUnavailable(format) => write!(f, "the clipboard does not offer {}", format),
+            InvalidUtf8(_) => write!(f, "the clipboard contents are not valid UTF-8"),
+            Timeout => write!(f, "timed out waiting for the selection owner"),
+            Io(_) => write!(f, "couldn't transfer the clipboard contents"),
+            Backend(e) => write!(f, "clipboard error: {}", e),
+            NoBackendAvailable(attempts) => {
+                write!(f, "no clipboard backend is available")?;
+                for (i, (backend, e)) in attempts.iter().enumerate() {
+                    let separator = if i == 0 { " (" } else { "; " };
+                    write!(f, "{}{}: {}", separator, backend, e)?;
+                }
+                if !attempts.is_empty() {
+                    write!(f, ")")?;
+                }
+                Ok(())
+            }
+            HistoryFile(path, _) => {
+                write!(f, "couldn't access the history file {}", path.display())
+            }
+        }
+    }
+}
+
+impl StdError for ClipboardError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        use self::ClipboardError::*;
+        match self {
+            InvalidUtf8(e) => Some(e),
+            Io(e) | HistoryFile(_, e) => Some(e),
+            // already part of the message
+            NoDisplayServer(_) | Backend(_) | NoBackendAvailable(_) => None,
+            Empty | UnsupportedProtocol(_) | Unsupported(_) | FormatUnavailable(_) | Timeout => {
+                None
+            }
+        }
+    }
+}
+
+impl From<FromUtf8Error> for ClipboardError {
+    fn from(err: FromUtf8Error) -> ClipboardError {
+        ClipboardError::InvalidUtf8(err)
+    }
+}
+
+impl From<io::Error> for ClipboardError {
+    fn from(err: io::Error) -> ClipboardError {
+        ClipboardError::Io(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_backend_lists_attempts() {
+        let err = ClipboardError::NoBackendAvailable(vec![
+            (
+                "wayland".to_owned(),
+                ClipboardError::Unsupported("data-control".to_owned()),
+            ),
+            ("x11".to_owned(), ClipboardError::Timeout),
+        ]);
+        assert_eq!(
+            err.to_string(),
+            "no clipboard backend is available (wayland: data-control is not supported; \
+             x11: timed out waiting for the selection owner)"
+        );
+        // chain printers would show the attempts twice otherwise
+        assert!(err.source().is_none());
+    }
+
+    #[test]
+    fn invalid_utf8_keeps_source() {
+        let err: ClipboardError = String::from_utf8(vec![0xff]).unwrap_err().into();
+        assert!(matches!(err, ClipboardError::InvalidUtf8(_)));
+        assert!(err.source().is_some());
+
+        // errors must be usable across threads, e.g. with anyhow
+        fn assert_send_sync<T: Send + Sync + 'static>(_: &T) {}
+        assert_send_sync(&err);
+    }
+}
@@ -0,0 +1,469 @@
+//! A history of clipboard contents, e.g. for clipboard managers.
+
+use crate::common::*;
+use crate::error::{ClipboardError, Result};
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::time::SystemTime;
+
+/// How many entries a `ClipboardHistory` keeps unless configured
+/// otherwise.
+pub const DEFAULT_MAX_ENTRIES: usize = 100;
+
+/// How many bytes of contents a `ClipboardHistory` keeps unless
+/// configured otherwise.
+pub const DEFAULT_MAX_BYTES: usize = 16 * 1024 * 1024;
+
+/// Something that was copied, as recorded by a `ClipboardHistory`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HistoryEntry {
+    /// When the contents were recorded
+    pub timestamp: SystemTime,
+    /// The selection the contents were copied to
+    pub kind: ClipboardKind,
+    /// Every MIME type the copying application offered
+    pub mime_types: Vec<String>,
+    /// The recorded representations, see `ClipboardHistory::with_mime_types`
+    pub contents: Vec<ClipboardContent>,
+}
+
+impl HistoryEntry {
+    /// The plain text representation, if one was recorded.
+    pub fn text(&self) -> Option<&str> {
+        self.contents.iter().find_map(|content| match content {
+            ClipboardContent::Text(text) => Some(text.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Whether the copying application asked clipboard managers not to
+    /// record the contents, by offering `x-kde-passwordManagerHint`.
+    pub fn is_sensitive(&self) -> bool {
+        self.mime_types
+            .iter()
+            .any(|mime_type| mime_type == PASSWORD_MANAGER_HINT)
+    }
+
+    /// The number of bytes of the recorded representations.
+    pub fn size(&self) -> usize {
+        self.contents.iter().map(content_size).sum()
+    }
+}
+
+fn content_size(content: &ClipboardContent) -> usize {
+    match content {
+        ClipboardContent::Text(text)
+        | ClipboardContent::Html(text)
+        | ClipboardContent::Rtf(text) => text.len(),
+        ClipboardContent::Png(data) | ClipboardContent::Other { data, .. } => data.len(),
+        ClipboardContent::UriList(uris) => uris.iter().map(|uri| uri.len() + 2).sum(),
+    }
+}
+
+/// The most recent contents of the clipboard and the primary selection.
+///
+/// Entries are ordered newest first. Copying something that is already
+/// in the history moves its entry to the front instead of adding another
+/// one. The oldest entries are dropped once there are more than
+/// `max_entries` of them or their contents take up more than
+/// `max_bytes`.
+///
+/// # Example
+///
+/// ```noop
+/// use cli_clipboard::history::ClipboardHistory;
+/// use cli_clipboard::linux_clipboard::LinuxClipboardContext;
+///
+/// let mut history = ClipboardHistory::new().with_max_entries(50);
+/// history
+///     .record_changes(LinuxClipboardContext::builder(), |history| {
+///         println!("{:?}", history.get(0).and_then(|entry| entry.text()));
+///         true
+///     })
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct ClipboardHistory {
+    entries: VecDeque<HistoryEntry>,
+    bytes: usize,
+    max_entries: usize,
+    max_bytes: usize,
+    mime_types: Vec<String>,
+    record_sensitive: bool,
+}
+
+impl Default for ClipboardHistory {
+    fn default() -> ClipboardHistory {
+        ClipboardHistory {
+            entries: VecDeque::new(),
+            bytes: 0,
+            max_entries: DEFAULT_MAX_ENTRIES,
+            max_bytes: DEFAULT_MAX_BYTES,
+            mime_types: vec!["text/plain;charset=utf-8".to_owned()],
+            record_sensitive: false,
+        }
+    }
+}
+
+impl ClipboardHistory {
+    /// Creates an empty history that records plain text.
+    pub fn new() -> ClipboardHistory {
+        ClipboardHistory::default()
+    }
+
+    /// Sets how many entries are kept. Defaults to
+    /// `DEFAULT_MAX_ENTRIES`.
+    pub fn with_max_entries(mut self, max_entries: usize) -> ClipboardHistory {
+        self.max_entries = max_entries;
+        self.trim();
+        self
+    }
+
+    /// Sets how many bytes of contents are kept. Defaults to
+    /// `DEFAULT_MAX_BYTES`.
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> ClipboardHistory {
+        self.max_bytes = max_bytes;
+        self.trim();
+        self
+    }
+
+    /// Sets the MIME types whose contents `record` reads, e.g. to also
+    /// keep `text/html` or `image/png`. Defaults to plain text only.
+    pub fn with_mime_types(mut self, mime_types: Vec<String>) -> ClipboardHistory {
+        self.mime_types = mime_types;
+        self
+    }
+
+    /// Sets whether `record` keeps contents the copying application
+    /// marked as sensitive, see `HistoryEntry::is_sensitive`. Defaults
+    /// to false.
+    pub fn with_record_sensitive(mut self, record_sensitive: bool) -> ClipboardHistory {
+        self.record_sensitive = record_sensitive;
+        self
+    }
+
+    /// The number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether there are no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The number of bytes of contents held by all entries.
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// The entry at `index`, where 0 is the most recent one.
+    pub fn get(&self, index: usize) -> Option<&HistoryEntry> {
+        self.entries.get(index)
+    }
+
+    /// The entries, newest first.
+    pub fn iter(&self) -> impl Iterator<Item = &HistoryEntry> {
+        self.entries.iter()
+    }
+
+    /// The entries whose text contains `substring`, newest first.
+    pub fn search<'a>(&'a self, substring: &'a str) -> impl Iterator<Item = &'a HistoryEntry> {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.text().is_some_and(|text| text.contains(substring)))
+    }
+
+    /// The entries recorded within `range`, newest first.
+    pub fn between(&self, range: Range<SystemTime>) -> impl Iterator<Item = &HistoryEntry> {
+        self.entries
+            .iter()
+            .filter(move |entry| range.contains(&entry.timestamp))
+    }
+
+    /// Removes all entries.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.bytes = 0;
+    }
+
+    /// Adds `entry` as the most recent one.
+    ///
+    /// An existing entry with the same contents is replaced. Returns
+    /// false without adding anything if `entry` holds no contents or
+    /// more than `max_bytes` of them.
+    pub fn push(&mut self, entry: HistoryEntry) -> bool {
+        let size = entry.size();
+        if entry.contents.is_empty() || size > self.max_bytes || self.max_entries == 0 {
+            return false;
+        }
+        if let Some(index) = self
+            .entries
+            .iter()
+            .position(|existing| existing.contents == entry.contents)
+        {
+            self.remove(index);
+        }
+        self.bytes += size;
+        self.entries.push_front(entry);
+        self.trim();
+        true
+    }
+
+    /// Removes and returns the entry at `index`.
+    pub fn remove(&mut self, index: usize) -> Option<HistoryEntry> {
+        let entry = self.entries.remove(index)?;
+        self.bytes -= entry.size();
+        Some(entry)
+    }
+
+    /// Reads the current contents of `provider` and adds them as copied
+    /// to `kind`.
+    ///
+    /// Only the MIME types set with `with_mime_types` are read. Returns
+    /// false if `provider` is empty or offers none of them, if the
+    /// contents are sensitive and not recorded, see
+    /// `with_record_sensitive`, or if they are rejected by `push`.
+    pub fn record(
+        &mut self,
+        kind: ClipboardKind,
+        provider: &mut dyn ClipboardProvider,
+    ) -> Result<bool> {
+        let mime_types = match provider.available_formats() {
+            Ok(mime_types) => mime_types,
+            // providers that can't list formats only hold text
+            Err(ClipboardError::Unsupported(_)) => vec!["text/plain;charset=utf-8".to_owned()],
+            Err(e) => return Err(e),
+        };
+        if !self.record_sensitive
+            && mime_types
+                .iter()
+                .any(|mime_type| mime_type == PASSWORD_MANAGER_HINT)
+        {
+            return Ok(false);
+        }
+
+        let mut contents = Vec::new();
+        for wanted in &self.mime_types {
+            let offered = mime_types.iter().any(|mime_type| {
+                mime_type == wanted || is_plain_text(mime_type) && is_plain_text(wanted)
+            });
+            if !offered {
+                continue;
+            }
+            let data = match provider.get_bytes(wanted) {
+                Ok(data) => data,
+                Err(ClipboardError::Empty) | Err(ClipboardError::FormatUnavailable(_)) => continue,
+                Err(e) => return Err(e),
+            };
+            contents.push(
+                ClipboardContent::from_bytes(wanted, data.clone()).unwrap_or_else(|_| {
+                    ClipboardContent::Other {
+                        mime_type: wanted.clone(),
+                        data,
+                    }
+                }),
+            );
+        }
+
+        Ok(self.push(HistoryEntry {
+            timestamp: SystemTime::now(),
+            kind,
+            mime_types,
+            contents,
+        }))
+    }
+
+    /// Copies the entry at `index` back to `provider`, offering all of
+    /// its recorded representations. Returns false if there is no entry
+    /// at `index`.
+    pub fn restore(&self, index: usize, provider: &mut dyn ClipboardProvider) -> Result<bool> {
+        match self.entries.get(index) {
+            Some(entry) => provider
+                .set_content_multi(entry.contents.clone())
+                .map(|()| true),
+            None => Ok(false),
+        }
+    }
+
+    /// Drops the oldest entries until both caps are met.
+    fn trim(&mut self) {
+        while self.entries.len() > self.max_entries || self.bytes > self.max_bytes {
+            match self.entries.pop_back() {
+                Some(entry) => self.bytes -= entry.size(),
+                None => break,
+            }
+        }
+    }
+}
+
+#[cfg(all(
+    unix,
+    not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+))]
+impl ClipboardHistory {
+    /// Records every change of the clipboard and the primary selection
+    /// until `callback` returns false, using `LinuxClipboardContext::watch`.
+    ///
+    /// `builder` picks the backend, its kind is ignored. Changes whose
+    /// contents can't be read, because the copying application went
+    /// away or did not answer in time, are skipped. Blocks the calling
+    /// thread while recording, returns Err once the connection to the
+    /// display server is lost.
+    pub fn record_changes<F>(
+        &mut self,
+        builder: crate::linux_clipboard::LinuxClipboardBuilder,
+        mut callback: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&ClipboardHistory) -> bool,
+    {
+        let mut clipboard = builder.clone().kind(ClipboardKind::Clipboard).build()?;
+        // not every compositor supports the primary selection
+        let mut primary = builder.kind(ClipboardKind::Primary).build().ok();
+
+        let mut last_error = None;
+        for event in clipboard.watch()? {
+            // the watcher ends after an error it can't recover from
+            let event = match event {
+                Ok(event) => event,
+                Err(e) => {
+                    last_error = Some(e);
+                    continue;
+                }
+            };
+            if event.mime_types.is_empty() {
+                continue;
+            }
+            let provider = match (event.kind, &mut primary) {
+                (ClipboardKind::Primary, Some(primary)) => primary,
+                (ClipboardKind::Primary, None) => continue,
+                _ => &mut clipboard,
+            };
+            // other errors only concern this change
+            if let Err(e @ ClipboardError::NoDisplayServer(_)) = self.record(event.kind, provider) {
+                return Err(e);
+            }
+            if !callback(self) {
+                return Ok(());
+            }
+        }
+        last_error.map_or(Ok(()), Err)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::memory_clipboard::{MemoryClipboard, MemoryClipboardContext};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    /// A plain text entry recorded `millis` after the epoch.
+    pub(crate) fn entry(text: &str, millis: u64) -> HistoryEntry {
+        HistoryEntry {
+            timestamp: UNIX_EPOCH + Duration::from_millis(millis),
+            kind: ClipboardKind::Clipboard,
+            mime_types: vec!["text/plain;charset=utf-8".to_owned()],
+            contents: vec![ClipboardContent::Text(text.to_owned())],
+        }
+    }
+
+    #[test]
+    fn dedupe_and_caps() {
+        let mut history = ClipboardHistory::new()
+            .with_max_entries(3)
+            .with_max_bytes(10);
+        assert!(history.push(entry("one", 0)));
+        assert!(history.push(entry("two", 0)));
+        assert!(history.push(entry("one", 0)));
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.get(0).unwrap().text(), Some("one"));
+        assert_eq!(history.bytes(), 6);
+
+        assert!(history.push(entry("three", 0)));
+        assert_eq!(
+            history
+                .iter()
+                .map(|e| e.text().unwrap())
+                .collect::<Vec<_>>(),
+            vec!["three", "one"]
+        );
+        assert!(!history.push(entry("far too long", 0)));
+        assert!(history.push(entry("a", 0)));
+        assert!(history.push(entry("b", 0)));
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.get(2).unwrap().text(), Some("three"));
+    }
+
+    #[test]
+    fn search_and_between() {
+        let mut history = ClipboardHistory::new();
+        history.push(entry("one", 1000));
+        history.push(entry("two", 2000));
+        history.push(entry("three", 3000));
+
+        let texts = |entries: &mut dyn Iterator<Item = &HistoryEntry>| {
+            entries
+                .map(|e| e.text().unwrap().to_owned())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(texts(&mut history.search("e")), vec!["three", "one"]);
+        assert_eq!(history.search("x").count(), 0);
+        let range = UNIX_EPOCH + Duration::from_millis(1500)..UNIX_EPOCH + Duration::from_secs(3);
+        assert_eq!(texts(&mut history.between(range)), vec!["two"]);
+    }
+
+    #[test]
+    fn record_and_restore() {
+        let clipboard = MemoryClipboard::new();
+        let mut ctx = MemoryClipboardContext::with_clipboard(clipboard.clone());
+        let mut history = ClipboardHistory::new()
+            .with_mime_types(vec!["text/plain".to_owned(), "text/html".to_owned()]);
+
+        assert!(!history.record(ClipboardKind::Clipboard, &mut ctx).unwrap());
+        ctx.set_content_multi(vec![
+            ClipboardContent::Html("<b>first</b>".to_owned()),
+            ClipboardContent::Text("first".to_owned()),
+            ClipboardContent::Png(vec![0x89]),
+        ])
+        .unwrap();
+        assert!(history.record(ClipboardKind::Clipboard, &mut ctx).unwrap());
+        ctx.set_contents("second".to_owned()).unwrap();
+        assert!(history.record(ClipboardKind::Clipboard, &mut ctx).unwrap());
+
+        let first = history.get(1).unwrap();
+        assert_eq!(first.mime_types.len(), 3);
+        assert_eq!(first.contents.len(), 2);
+
+        assert!(history.restore(1, &mut ctx).unwrap());
+        assert_eq!(ctx.get_contents().unwrap(), "first");
+        assert_eq!(
+            ctx.get_content("text/html").unwrap(),
+            ClipboardContent::Html("<b>first</b>".to_owned())
+        );
+        assert!(!history.restore(5, &mut ctx).unwrap());
+    }
+
+    #[test]
+    fn sensitive_copies_are_skipped() {
+        let mut ctx = MemoryClipboardContext::with_clipboard(MemoryClipboard::new());
+        let mut history = ClipboardHistory::new();
+        let mut everything = ClipboardHistory::new().with_record_sensitive(true);
+
+        ctx.set_contents("public".to_owned()).unwrap();
+        assert!(history.record(ClipboardKind::Clipboard, &mut ctx).unwrap());
+        assert!(everything
+            .record(ClipboardKind::Clipboard, &mut ctx)
+            .unwrap());
+        ctx.set_sensitive_contents("hunter2".to_owned()).unwrap();
+        assert_eq!(ctx.get_contents().unwrap(), "hunter2");
+        assert!(!history.record(ClipboardKind::Clipboard, &mut ctx).unwrap());
+        assert!(everything
+            .record(ClipboardKind::Clipboard, &mut ctx)
+            .unwrap());
+
+        assert_eq!(history.len(), 1);
+        assert!(everything.get(0).unwrap().is_sensitive());
+        assert!(!everything.get(1).unwrap().is_sensitive());
+    }
+}
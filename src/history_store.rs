@@ -0,0 +1,413 @@
+//! Clipboard history that survives restarts.
+
+use crate::common::*;
+use crate::error::{ClipboardError, Result};
+use crate::history::{ClipboardHistory, HistoryEntry};
+use std::env;
+use std::fs::{self, DirBuilder, File, OpenOptions, Permissions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Starts every line, so that the format can change without misreading
+// older files.
+const FORMAT_VERSION: &str = "v1";
+
+/// An append-only file of `HistoryEntry`s.
+///
+/// Each recorded entry is appended as a single line, so that a crash
+/// loses at most the entry being written. Copying the same contents
+/// again appends another line, `load` keeps only the most recent one and
+/// `compact` rewrites the file without the duplicates. `search` and
+/// `between` scan the file without loading all of it.
+///
+/// The file is only readable and writable by its owner (mode 0600).
+/// Entries the copying application marked as sensitive, see
+/// `HistoryEntry::is_sensitive`, are not stored unless enabled with
+/// `with_exclude_sensitive(false)`.
+///
+/// # Example
+///
+/// ```noop
+/// use cli_clipboard::history_store::HistoryStore;
+/// use std::time::{Duration, SystemTime};
+///
+/// let store = HistoryStore::open().unwrap();
+/// let now = SystemTime::now();
+/// let yesterday = now - Duration::from_secs(24 * 60 * 60);
+/// for entry in store.between(yesterday..now).unwrap() {
+///     println!("{}", entry.text().unwrap_or("(not text)"));
+/// }
+/// ```
+pub struct HistoryStore {
+    path: PathBuf,
+    exclude_sensitive: bool,
+}
+
+impl HistoryStore {
+    /// Opens the store at `default_path`.
+    pub fn open() -> Result<HistoryStore> {
+        let path = HistoryStore::default_path().ok_or_else(|| {
+            ClipboardError::Unsupported("storing history without XDG_DATA_HOME or HOME".to_owned())
+        })?;
+        HistoryStore::open_at(path)
+    }
+
+    /// `$XDG_DATA_HOME/cli-clipboard/history`, where `XDG_DATA_HOME`
+    /// defaults to `~/.local/share`.
+    pub fn default_path() -> Option<PathBuf> {
+        let data_home = env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute())
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))?;
+        Some(data_home.join("cli-clipboard").join("history"))
+    }
+
+    /// Opens the store at `path`, creating the file and its directory if
+    /// needed. The permissions of an existing file are restricted to
+    /// 0600.
+    pub fn open_at<P: Into<PathBuf>>(path: P) -> Result<HistoryStore> {
+        let path = path.into();
+        if let Some(dir) = path.parent() {
+            DirBuilder::new()
+                .recursive(true)
+                .mode(0o700)
+                .create(dir)
+                .map_err(file_error(dir))?;
+        }
+        open_private(&path, OpenOptions::new().append(true))?;
+        Ok(HistoryStore {
+            path,
+            exclude_sensitive: true,
+        })
+    }
+
+    /// Sets whether sensitive entries are left out. Defaults to true.
+    pub fn with_exclude_sensitive(mut self, exclude_sensitive: bool) -> HistoryStore {
+        self.exclude_sensitive = exclude_sensitive;
+        self
+    }
+
+    /// The file this store reads and appends to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends `entry` to the file. Returns false if it was left out as
+    /// sensitive.
+    pub fn append(&self, entry: &HistoryEntry) -> Result<bool> {
+        if self.excludes(entry) {
+            return Ok(false);
+        }
+        let mut file = open_private(&self.path, OpenOptions::new().read(true).append(true))?;
+        let mut line = encode(entry);
+        let mut write = || -> io::Result<()> {
+            // start a new line after one whose writing was interrupted
+            if file.seek(SeekFrom::End(0))? > 0 {
+                let mut last = [0];
+                file.seek(SeekFrom::End(-1))?;
+                file.read_exact(&mut last)?;
+                if last[0] != b'\n' {
+                    line.insert(0, '\n');
+                }
+            }
+            file.write_all(line.as_bytes())
+        };
+        write().map_err(file_error(&self.path))?;
+        Ok(true)
+    }
+
+    /// Reads all stored entries into a new `ClipboardHistory` without
+    /// caps. Use `load_into` to keep only the most recent ones.
+    pub fn load(&self) -> Result<ClipboardHistory> {
+        let mut history = ClipboardHistory::new()
+            .with_max_entries(usize::MAX)
+            .with_max_bytes(usize::MAX);
+        self.load_into(&mut history)?;
+        Ok(history)
+    }
+
+    /// Adds the stored entries to `history`, oldest first, so that its
+    /// deduplication and caps apply.
+    ///
+    /// Lines that can't be read, e.g. because writing them was
+    /// interrupted, are skipped.
+    pub fn load_into(&self, history: &mut ClipboardHistory) -> Result<()> {
+        self.scan(|entry| {
+            history.push(entry);
+        })
+    }
+
+    /// The stored entries whose text contains `substring`, newest first
+    /// and without duplicates, like `ClipboardHistory::search`.
+    pub fn search(&self, substring: &str) -> Result<Vec<HistoryEntry>> {
+        self.find(|entry| entry.text().is_some_and(|text| text.contains(substring)))
+    }
+
+    /// The stored entries recorded within `range`, newest first and
+    /// without duplicates, like `ClipboardHistory::between`.
+    pub fn between(&self, range: Range<SystemTime>) -> Result<Vec<HistoryEntry>> {
+        self.find(|entry| range.contains(&entry.timestamp))
+    }
+
+    /// Replaces the file with the entries of `history`, usually one
+    /// returned by `load`, dropping duplicates and entries beyond its
+    /// caps.
+    ///
+    /// The file is replaced atomically. Entries appended by other
+    /// processes since `history` was loaded are lost.
+    pub fn compact(&self, history: &ClipboardHistory) -> Result<()> {
+        let temp = self.path.with_extension("compacting");
+        let mut file = open_private(&temp, OpenOptions::new().write(true).truncate(true))?;
+        let entries: Vec<&HistoryEntry> = history.iter().collect();
+        let write = || -> io::Result<()> {
+            for entry in entries.into_iter().rev() {
+                if !self.excludes(entry) {
+                    file.write_all(encode(entry).as_bytes())?;
+                }
+            }
+            file.sync_all()
+        };
+        write().map_err(file_error(&temp))?;
+        drop(file);
+        fs::rename(&temp, &self.path).map_err(file_error(&self.path))
+    }
+
+    fn excludes(&self, entry: &HistoryEntry) -> bool {
+        self.exclude_sensitive && entry.is_sensitive()
+    }
+
+    /// Calls `f` with every readable entry that isn't excluded, oldest
+    /// first.
+    fn scan<F: FnMut(HistoryEntry)>(&self, mut f: F) -> Result<()> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(file_error(&self.path)(e)),
+        };
+        for line in BufReader::new(file).split(b'\n') {
+            if let Some(entry) = decode(&line.map_err(file_error(&self.path))?) {
+                if !self.excludes(&entry) {
+                    f(entry);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The most recent entry for each contents matching `predicate`,
+    /// newest first.
+    fn find<P: Fn(&HistoryEntry) -> bool>(&self, predicate: P) -> Result<Vec<HistoryEntry>> {
+        let mut found: Vec<HistoryEntry> = Vec::new();
+        self.scan(|entry| {
+            if predicate(&entry) {
+                found.retain(|existing| existing.contents != entry.contents);
+                found.push(entry);
+            }
+        })?;
+        found.reverse();
+        Ok(found)
+    }
+}
+
+/// Opens `path` with `options`, creating it if needed, and restricts it
+/// to its owner.
+fn open_private(path: &Path, options: &mut OpenOptions) -> Result<File> {
+    let file = options
+        .create(true)
+        .mode(0o600)
+        .open(path)
+        .map_err(file_error(path))?;
+    file.set_permissions(Permissions::from_mode(0o600))
+        .map_err(file_error(path))?;
+    Ok(file)
+}
+
+/// Wraps errors accessing `path` so that they name the file.
+fn file_error(path: &Path) -> impl Fn(io::Error) -> ClipboardError + '_ {
+    move |e| ClipboardError::HistoryFile(path.to_owned(), e)
+}
+
+fn kind_name(kind: ClipboardKind) -> &'static str {
+    match kind {
+        ClipboardKind::Clipboard => "clipboard",
+        ClipboardKind::Primary => "primary",
+        ClipboardKind::Secondary => "secondary",
+        ClipboardKind::Both => "both",
+    }
+}
+
+fn parse_kind(name: &str) -> Option<ClipboardKind> {
+    Some(match name {
+        "clipboard" => ClipboardKind::Clipboard,
+        "primary" => ClipboardKind::Primary,
+        "secondary" => ClipboardKind::Secondary,
+        "both" => ClipboardKind::Both,
+        _ => return None,
+    })
+}
+
+/// Encodes `entry` as a line of space separated fields: the format
+/// version, the timestamp in milliseconds, the selection, the offered
+/// MIME types and one `mime_type:data` field per representation, with
+/// MIME types and data in base64.
+fn encode(entry: &HistoryEntry) -> String {
+    let millis = entry
+        .timestamp
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let mut line = format!(
+        "{} {} {} {}",
+        FORMAT_VERSION,
+        millis,
+        kind_name(entry.kind),
+        base64::encode(entry.mime_types.join("\n"))
+    );
+    for content in &entry.contents {
+        line.push(' ');
+        line.push_str(&base64::encode(content.mime_type()));
+        line.push(':');
+        line.push_str(&base64::encode(content.clone().into_bytes()));
+    }
+    line.push('\n');
+    line
+}
+
+fn decode(line: &[u8]) -> Option<HistoryEntry> {
+    let decode_string = |field: &str| String::from_utf8(base64::decode(field).ok()?).ok();
+
+    let line = std::str::from_utf8(line).ok()?;
+    let mut fields = line.split(' ');
+    if fields.next()? != FORMAT_VERSION {
+        return None;
+    }
+    let millis = fields.next()?.parse().ok()?;
+    let kind = parse_kind(fields.next()?)?;
+    let mime_types = decode_string(fields.next()?)?;
+    let mime_types = if mime_types.is_empty() {
+        Vec::new()
+    } else {
+        mime_types.split('\n').map(str::to_owned).collect()
+    };
+    let contents = fields
+        .map(|field| {
+            let (mime_type, data) = field.split_once(':')?;
+            let mime_type = decode_string(mime_type)?;
+            let data = base64::decode(data).ok()?;
+            Some(
+                ClipboardContent::from_bytes(&mime_type, data.clone())
+                    .unwrap_or(ClipboardContent::Other { mime_type, data }),
+            )
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(HistoryEntry {
+        timestamp: UNIX_EPOCH + Duration::from_millis(millis),
+        kind,
+        mime_types,
+        contents,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::tests::entry;
+
+    fn temp_store(name: &str) -> (PathBuf, HistoryStore) {
+        let dir = env::temp_dir().join(format!("cli-clipboard-{}-{}", name, std::process::id()));
+        let store = HistoryStore::open_at(dir.join("history")).unwrap();
+        (dir, store)
+    }
+
+    #[test]
+    fn append_load_and_compact() {
+        let (dir, store) = temp_store("history");
+        let path = store.path().to_owned();
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+
+        let html = HistoryEntry {
+            kind: ClipboardKind::Primary,
+            mime_types: vec!["text/html".to_owned(), "text/plain".to_owned()],
+            contents: vec![
+                ClipboardContent::Html("<b>caf\u{e9}</b>".to_owned()),
+                ClipboardContent::Other {
+                    mime_type: "text/plain".to_owned(),
+                    data: b"caf\xe9".to_vec(),
+                },
+            ],
+            ..entry("", 1000)
+        };
+        let secret = HistoryEntry {
+            mime_types: vec!["text/plain".to_owned(), PASSWORD_MANAGER_HINT.to_owned()],
+            ..entry("hunter2", 2000)
+        };
+        let no_mime_types = |text, millis| HistoryEntry {
+            mime_types: Vec::new(),
+            ..entry(text, millis)
+        };
+        assert!(store.append(&html).unwrap());
+        assert!(store.append(&no_mime_types("foo", 3000)).unwrap());
+        assert!(!store.append(&secret).unwrap());
+        assert!(store.append(&no_mime_types("foo", 4000)).unwrap());
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"v1 5000 primary")
+            .unwrap();
+
+        assert!(store.append(&entry("bar", 6000)).unwrap());
+        let mut history = store.load().unwrap();
+        assert_eq!(history.remove(0).unwrap().text(), Some("bar"));
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.get(0).unwrap(), &no_mime_types("foo", 4000));
+        assert_eq!(history.get(1).unwrap(), &html);
+
+        store.compact(&history).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 2);
+        assert_eq!(store.load().unwrap().len(), 2);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn errors_name_the_file() {
+        let (dir, store) = temp_store("history-errors");
+        let blocked = store.path().join("history");
+        match HistoryStore::open_at(&blocked) {
+            Err(ClipboardError::HistoryFile(path, _)) => assert_eq!(path, store.path()),
+            _ => panic!("expected an error naming the history file"),
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn load_and_search_beyond_caps() {
+        let (dir, store) = temp_store("history-search");
+        let count = crate::history::DEFAULT_MAX_ENTRIES as u64 + 20;
+        for i in 0..count {
+            store.append(&entry(&format!("entry {}", i), i)).unwrap();
+        }
+        store.append(&entry("entry 1", count)).unwrap();
+
+        assert_eq!(store.load().unwrap().len(), count as usize);
+        let found = store.search("entry 1").unwrap();
+        assert_eq!(found.len(), 1 + 10 + 20);
+        assert_eq!(found[0], entry("entry 1", count));
+        assert_eq!(found.last().unwrap().text(), Some("entry 10"));
+
+        let range = UNIX_EPOCH..UNIX_EPOCH + Duration::from_millis(2);
+        let found = store.between(range).unwrap();
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].text(), Some("entry 1"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
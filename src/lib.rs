@@ -28,6 +28,8 @@ limitations under the License.
 //!
 //! On Linux it will first attempt to setup a Wayland clipboard provider.  If that
 //! fails it will then fallback to the X11 clipboard provider.
+//! Use `linux_clipboard::LinuxClipboardContext::builder` or the
+//! `CLI_CLIPBOARD_BACKEND` environment variable to force a backend.
 //!
 //! ## Examples
 //!
@@ -40,15 +42,10 @@ limitations under the License.
 //! let the_string = "Hello, world!";
 //! ctx.set_contents(the_string.to_owned()).unwrap();
 //! assert_eq!(ctx.get_contents().unwrap(), the_string);
-//! ctx.clear();
-//! // clearing the clipboard causes get_contents to return Err on macos and windows
-//! if cfg!(any(windows, target_os = "macos")) {
-//!    if ctx.get_contents().is_ok() {
-//!        panic!("Should be Err");
-//!    }
-//! } else {
-//!    assert_eq!(ctx.get_contents().unwrap(), "");
-//! }
+//! ctx.clear().unwrap();
+//! // an empty clipboard is reported as ClipboardError::Empty on every platform
+//! assert!(ctx.get_contents().is_err());
+//! assert_eq!(ctx.get_contents_opt().unwrap(), None);
 //! ```
 //!
 //! Using the helper functions:
@@ -71,10 +68,17 @@ extern crate x11_clipboard as x11_clipboard_crate;
 #[macro_use]
 extern crate objc;
 
-use anyhow::Result;
+use std::time::Duration;
 
 mod common;
-pub use common::ClipboardProvider;
+pub use common::{ClipboardContent, ClipboardEvent, ClipboardKind, ClipboardProvider};
+
+mod error;
+pub use error::{ClipboardError, Result};
+
+pub mod history;
+
+pub mod auto_clear;
 
 #[cfg(all(
     unix,
@@ -88,12 +92,33 @@ pub mod wayland_clipboard;
 ))]
 pub mod x11_clipboard;
 
+#[cfg(all(
+    unix,
+    not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+))]
+mod x11_server;
+
 #[cfg(all(
     unix,
     not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
 ))]
 pub mod linux_clipboard;
 
+#[cfg(unix)]
+pub mod command_clipboard;
+
+#[cfg(unix)]
+pub mod history_store;
+
+#[cfg(unix)]
+pub mod osc52_clipboard;
+
+#[cfg(unix)]
+pub mod tmux_clipboard;
+
+#[cfg(any(test, feature = "mock"))]
+pub mod memory_clipboard;
+
 #[cfg(windows)]
 pub mod windows_clipboard;
 
@@ -112,6 +137,24 @@ pub type ClipboardContext = windows_clipboard::WindowsClipboardContext;
 #[cfg(target_os = "macos")]
 pub type ClipboardContext = macos_clipboard::MacOSClipboardContext;
 
+/// Create the platform's default clipboard context as a trait object
+///
+/// Useful for choosing a provider at runtime, since any
+/// `ClipboardProvider`, including third-party ones, can be stored as a
+/// `Box<dyn ClipboardProvider>`.
+///
+/// # Example
+/// ```
+/// use cli_clipboard::ClipboardProvider;
+///
+/// let mut ctx: Box<dyn ClipboardProvider> = cli_clipboard::boxed_context().unwrap();
+/// ctx.set_contents("testing".to_owned()).unwrap();
+/// assert_eq!(ctx.get_contents().unwrap(), "testing");
+/// ```
+pub fn boxed_context() -> Result<Box<dyn ClipboardProvider>> {
+    Ok(Box::new(ClipboardContext::new()?))
+}
+
 /// Get the current clipboard contents
 ///
 /// # Example
@@ -146,6 +189,27 @@ pub fn set_contents(data: String) -> Result<()> {
     Ok(())
 }
 
+/// Write a string to the clipboard and keep it available for a while
+///
+/// On Wayland and X11 this blocks until `duration` has elapsed (or,
+/// on X11, until another application takes over the selection),
+/// serving the contents to other applications in the meantime. The
+/// clipboard is then released, unless something else has been copied
+/// since. MacOS and Windows clipboard contents persist on their own,
+/// so there this returns as soon as the contents are set.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+///
+/// cli_clipboard::set_contents_for_duration("testing".to_owned(), Duration::from_millis(10))
+///     .unwrap();
+/// ```
+pub fn set_contents_for_duration(data: String, duration: Duration) -> Result<()> {
+    let mut ctx = ClipboardContext::new()?;
+    ctx.set_contents_for_duration(data, duration)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
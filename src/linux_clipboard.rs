@@ -1,50 +1,506 @@
+use crate::command_clipboard::CommandClipboardContext;
 use crate::common::*;
-use crate::wayland_clipboard::WaylandClipboardContext;
-use crate::x11_clipboard::{Clipboard, X11ClipboardContext};
-use anyhow::Result;
+use crate::error::{ClipboardError, Result};
+use crate::osc52_clipboard::Osc52ClipboardContext;
+use crate::tmux_clipboard::TmuxClipboardContext;
+use crate::wayland_clipboard::{WaylandClipboardContext, WaylandWatcher};
+use crate::x11_clipboard::{Clipboard, X11ClipboardContext, X11Watcher};
+use std::env;
+use std::fmt;
+use std::io::{Read, Write};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Environment variable that forces a backend, overriding the builder.
+/// Accepts the same names as `Backend::from_str`.
+pub const BACKEND_ENV_VAR: &str = "CLI_CLIPBOARD_BACKEND";
+
+/// Clipboard backends available on Linux.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    Wayland,
+    X11,
+    /// The terminal's clipboard, see `Osc52ClipboardContext`. Only used
+    /// if forced or added with `LinuxClipboardBuilder::fallback_to`.
+    Osc52,
+    /// The paste buffer of the surrounding tmux session, see
+    /// `TmuxClipboardContext`. Only used if forced or added with
+    /// `LinuxClipboardBuilder::fallback_to`.
+    Tmux,
+    /// External tools such as `wl-copy` or `xclip`, see
+    /// `CommandClipboardContext`. Only used if forced or added with
+    /// `LinuxClipboardBuilder::fallback_to`.
+    Command,
+}
+
+impl Backend {
+    const ALL: [Backend; 5] = [
+        Backend::Wayland,
+        Backend::X11,
+        Backend::Osc52,
+        Backend::Tmux,
+        Backend::Command,
+    ];
+    /// The backends that are detected automatically, in order.
+    const DETECTED: [Backend; 2] = [Backend::Wayland, Backend::X11];
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Backend::Wayland => write!(f, "wayland"),
+            Backend::X11 => write!(f, "x11"),
+            Backend::Osc52 => write!(f, "osc52"),
+            Backend::Tmux => write!(f, "tmux"),
+            Backend::Command => write!(f, "command"),
+        }
+    }
+}
+
+impl FromStr for Backend {
+    type Err = ClipboardError;
+
+    /// Parses a backend name, ignoring case.
+    fn from_str(name: &str) -> Result<Backend> {
+        Backend::ALL
+            .iter()
+            .copied()
+            .find(|backend| backend.to_string().eq_ignore_ascii_case(name.trim()))
+            .ok_or_else(|| ClipboardError::Unsupported(format!("clipboard backend {:?}", name)))
+    }
+}
+
+/// Environment variables that influence backend detection.
+const PROBED_ENV_VARS: [&str; 6] = [
+    "WAYLAND_DISPLAY",
+    "DISPLAY",
+    "XDG_SESSION_TYPE",
+    "TMUX",
+    "STY",
+    BACKEND_ENV_VAR,
+];
+
+/// Diagnostics about clipboard backend detection, see `probe`.
+#[derive(Debug)]
+pub struct Probe {
+    /// The relevant environment variables and their values, if set
+    pub env: Vec<(&'static str, Option<String>)>,
+    /// The outcome of connecting to each backend
+    pub attempts: Vec<(Backend, Result<()>)>,
+}
+
+impl fmt::Display for Probe {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, value) in &self.env {
+            match value {
+                Some(value) => writeln!(f, "{}={:?}", name, value)?,
+                None => writeln!(f, "{} is not set", name)?,
+            }
+        }
+        for (backend, result) in &self.attempts {
+            match result {
+                Ok(()) => writeln!(f, "{}: ok", backend)?,
+                Err(e) => writeln!(f, "{}: {}", backend, e)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reports the environment seen by backend detection and tries to
+/// connect to every backend, to help diagnose why no clipboard is
+/// available.
+///
+/// Unlike `LinuxClipboardContext::new`, every backend is tried, even if
+/// an earlier one works or `CLI_CLIPBOARD_BACKEND` is set.
+pub fn probe() -> Probe {
+    let env = PROBED_ENV_VARS
+        .iter()
+        .map(|&name| {
+            (
+                name,
+                env::var_os(name).map(|v| v.to_string_lossy().into_owned()),
+            )
+        })
+        .collect();
+    let attempts = Backend::ALL
+        .iter()
+        .map(|&backend| {
+            let result = LinuxClipboardBuilder::default().connect(backend);
+            (backend, result.map(drop))
+        })
+        .collect();
+    Probe { env, attempts }
+}
 
 enum LinuxContext {
     Wayland(WaylandClipboardContext),
     X11(X11ClipboardContext),
+    Osc52(Osc52ClipboardContext),
+    Tmux(TmuxClipboardContext),
+    Command(CommandClipboardContext),
+}
+
+/// Forwards a method call to whichever backend `$self` is connected to.
+macro_rules! dispatch {
+    ($self:ident, $context:ident => $call:expr) => {
+        match &mut $self.context {
+            LinuxContext::Wayland($context) => $call,
+            LinuxContext::X11($context) => $call,
+            LinuxContext::Osc52($context) => $call,
+            LinuxContext::Tmux($context) => $call,
+            LinuxContext::Command($context) => $call,
+        }
+    };
 }
 
 pub struct LinuxClipboardContext {
     context: LinuxContext,
+    kind: ClipboardKind,
+}
+
+/// Builder for a `LinuxClipboardContext` with explicit backend selection.
+///
+/// # Example
+///
+/// ```noop
+/// use cli_clipboard::linux_clipboard::{Backend, LinuxClipboardContext};
+///
+/// // only use X11, e.g. under XWayland
+/// let ctx = LinuxClipboardContext::builder()
+///     .prefer(Backend::X11)
+///     .fallback(false)
+///     .build()
+///     .unwrap();
+/// assert_eq!(ctx.backend(), Backend::X11);
+/// ```
+#[derive(Clone, Debug)]
+pub struct LinuxClipboardBuilder {
+    prefer: Backend,
+    fallback: bool,
+    extra: Vec<Backend>,
+    kind: ClipboardKind,
+    x11_timeout: Option<Duration>,
+}
+
+impl Default for LinuxClipboardBuilder {
+    fn default() -> LinuxClipboardBuilder {
+        LinuxClipboardBuilder {
+            prefer: Backend::Wayland,
+            fallback: true,
+            extra: Vec::new(),
+            kind: ClipboardKind::Clipboard,
+            x11_timeout: None,
+        }
+    }
+}
+
+impl LinuxClipboardBuilder {
+    /// Sets the backend to try first. Defaults to `Backend::Wayland`.
+    pub fn prefer(mut self, backend: Backend) -> LinuxClipboardBuilder {
+        self.prefer = backend;
+        self
+    }
+
+    /// Sets whether the other backends are tried if the preferred one
+    /// fails. Defaults to true.
+    pub fn fallback(mut self, fallback: bool) -> LinuxClipboardBuilder {
+        self.fallback = fallback;
+        self
+    }
+
+    /// Adds `backend` to the end of the fallback order, e.g. to use
+    /// `Backend::Osc52` when neither Wayland nor X11 can be reached.
+    pub fn fallback_to(mut self, backend: Backend) -> LinuxClipboardBuilder {
+        self.extra.push(backend);
+        self
+    }
+
+    /// Sets the selection to operate on. Defaults to
+    /// `ClipboardKind::Clipboard`.
+    ///
+    /// The selection has the same meaning on Wayland and X11, see
+    /// `ClipboardKind`. Backends that don't support the selection fail
+    /// to connect, so with fallback enabled e.g.
+    /// `ClipboardKind::Secondary` ends up using X11.
+    pub fn kind(mut self, kind: ClipboardKind) -> LinuxClipboardBuilder {
+        self.kind = kind;
+        self
+    }
+
+    /// Sets how long X11 reads wait for the selection owner, see
+    /// `X11ClipboardContext::set_timeout`.
+    pub fn x11_timeout(mut self, timeout: Duration) -> LinuxClipboardBuilder {
+        self.x11_timeout = Some(timeout);
+        self
+    }
+
+    /// Connects to the first backend that works.
+    ///
+    /// If `CLI_CLIPBOARD_BACKEND` is set, only the backend it names is
+    /// tried, regardless of the builder settings. An unknown name is an
+    /// error.
+    ///
+    /// If only one backend is tried its error is returned as is,
+    /// otherwise `ClipboardError::NoBackendAvailable` reports why each
+    /// backend failed.
+    pub fn build(self) -> Result<LinuxClipboardContext> {
+        let mut attempts = Vec::new();
+        for backend in self.order()? {
+            match self.connect(backend) {
+                Ok(context) => return Ok(context),
+                Err(err) => attempts.push((backend.to_string(), err)),
+            }
+        }
+        if attempts.len() == 1 {
+            Err(attempts.remove(0).1)
+        } else {
+            Err(ClipboardError::NoBackendAvailable(attempts))
+        }
+    }
+
+    fn connect(&self, backend: Backend) -> Result<LinuxClipboardContext> {
+        let unsupported = || {
+            Err(ClipboardError::Unsupported(format!(
+                "the {:?} selection with the {} backend",
+                self.kind, backend
+            )))
+        };
+        let context = match backend {
+            Backend::X11 => {
+                let mut context = X11ClipboardContext::<Clipboard>::new()?.with_kind(self.kind);
+                if let Some(timeout) = self.x11_timeout {
+                    context.set_timeout(timeout);
+                }
+                LinuxContext::X11(context)
+            }
+            Backend::Osc52 => {
+                LinuxContext::Osc52(Osc52ClipboardContext::new()?.with_kind(self.kind))
+            }
+            Backend::Wayland => {
+                let context = WaylandClipboardContext::new()?;
+                match self.kind {
+                    ClipboardKind::Secondary => return unsupported(),
+                    ClipboardKind::Primary if !context.supports_primary_selection() => {
+                        return Err(ClipboardError::UnsupportedProtocol(
+                            "the primary selection".to_owned(),
+                        ))
+                    }
+                    kind => LinuxContext::Wayland(context.with_kind(kind)),
+                }
+            }
+            _ if self.kind != ClipboardKind::Clipboard => return unsupported(),
+            Backend::Tmux => LinuxContext::Tmux(TmuxClipboardContext::new()?),
+            Backend::Command => LinuxContext::Command(CommandClipboardContext::new()?),
+        };
+        Ok(LinuxClipboardContext {
+            context,
+            kind: self.kind,
+        })
+    }
+
+    /// The backends to try, in order.
+    fn order(&self) -> Result<Vec<Backend>> {
+        if let Some(name) = env::var_os(BACKEND_ENV_VAR) {
+            let backend = name.to_string_lossy().parse()?;
+            return Ok(vec![backend]);
+        }
+
+        let mut order = vec![self.prefer];
+        if self.fallback {
+            for &backend in Backend::DETECTED.iter().chain(&self.extra) {
+                if !order.contains(&backend) {
+                    order.push(backend);
+                }
+            }
+        }
+        Ok(order)
+    }
+}
+
+impl LinuxClipboardContext {
+    /// Returns a builder to choose the backend explicitly.
+    pub fn builder() -> LinuxClipboardBuilder {
+        LinuxClipboardBuilder::default()
+    }
+
+    /// The selection this context operates on.
+    pub fn kind(&self) -> ClipboardKind {
+        self.kind
+    }
+
+    /// The backend this context is connected to.
+    pub fn backend(&self) -> Backend {
+        match self.context {
+            LinuxContext::Wayland(_) => Backend::Wayland,
+            LinuxContext::X11(_) => Backend::X11,
+            LinuxContext::Osc52(_) => Backend::Osc52,
+            LinuxContext::Tmux(_) => Backend::Tmux,
+            LinuxContext::Command(_) => Backend::Command,
+        }
+    }
+
+    /// Starts watching the regular clipboard and the primary selection
+    /// through the backend this context is connected to.
+    ///
+    /// The returned iterator blocks until the next change, see
+    /// `X11Watcher` and `WaylandWatcher`. Only the Wayland and X11
+    /// backends support watching.
+    ///
+    /// # Example
+    ///
+    /// ```noop
+    /// use cli_clipboard::{ClipboardContext, ClipboardProvider};
+    ///
+    /// let ctx = ClipboardContext::new().unwrap();
+    /// for event in ctx.watch().unwrap() {
+    ///     println!("{:?} changed", event.unwrap().kind);
+    /// }
+    /// ```
+    pub fn watch(&self) -> Result<ClipboardWatcher> {
+        let watcher = match self.context {
+            LinuxContext::Wayland(_) => Watcher::Wayland(WaylandWatcher::new()?),
+            LinuxContext::X11(_) => Watcher::X11(X11Watcher::new()?),
+            _ => {
+                return Err(ClipboardError::Unsupported(format!(
+                    "watching the clipboard with the {} backend",
+                    self.backend()
+                )))
+            }
+        };
+        Ok(ClipboardWatcher { watcher })
+    }
+
+    /// Calls `callback` for every change, like `watch`, until it returns
+    /// false. Blocks the calling thread while watching.
+    ///
+    /// Returns Err once the connection to the display server is lost,
+    /// the only error the watchers report.
+    pub fn watch_with<F>(&self, mut callback: F) -> Result<()>
+    where
+        F: FnMut(ClipboardEvent) -> bool,
+    {
+        for event in self.watch()? {
+            if !callback(event?) {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+enum Watcher {
+    Wayland(WaylandWatcher),
+    X11(X11Watcher),
+}
+
+/// Change events of the regular clipboard and the primary selection,
+/// returned by `LinuxClipboardContext::watch`.
+pub struct ClipboardWatcher {
+    watcher: Watcher,
+}
+
+impl Iterator for ClipboardWatcher {
+    type Item = Result<ClipboardEvent>;
+
+    fn next(&mut self) -> Option<Result<ClipboardEvent>> {
+        match &mut self.watcher {
+            Watcher::Wayland(watcher) => watcher.next(),
+            Watcher::X11(watcher) => watcher.next(),
+        }
+    }
 }
 
 impl ClipboardProvider for LinuxClipboardContext {
+    /// Tries Wayland first and falls back to X11, unless
+    /// `CLI_CLIPBOARD_BACKEND` forces a backend.
     fn new() -> Result<LinuxClipboardContext> {
-        match WaylandClipboardContext::new() {
-            Ok(context) => Ok(LinuxClipboardContext {
-                context: LinuxContext::Wayland(context),
-            }),
-            Err(_) => match X11ClipboardContext::<Clipboard>::new() {
-                Ok(context) => Ok(LinuxClipboardContext {
-                    context: LinuxContext::X11(context),
-                }),
-                Err(err) => Err(err),
-            },
-        }
+        LinuxClipboardContext::builder().build()
     }
 
     fn get_contents(&mut self) -> Result<String> {
-        match &mut self.context {
-            LinuxContext::Wayland(context) => context.get_contents(),
-            LinuxContext::X11(context) => context.get_contents(),
-        }
+        dispatch!(self, context => context.get_contents())
     }
 
     fn set_contents(&mut self, content: String) -> Result<()> {
-        match &mut self.context {
-            LinuxContext::Wayland(context) => context.set_contents(content),
-            LinuxContext::X11(context) => context.set_contents(content),
-        }
+        dispatch!(self, context => context.set_contents(content))
     }
 
     fn clear(&mut self) -> Result<()> {
-        match &mut self.context {
-            LinuxContext::Wayland(context) => context.clear(),
-            LinuxContext::X11(context) => context.clear(),
+        dispatch!(self, context => context.clear())
+    }
+
+    fn set_contents_for_duration(&mut self, content: String, duration: Duration) -> Result<()> {
+        dispatch!(self, context => context.set_contents_for_duration(content, duration))
+    }
+
+    fn clear_if_unchanged(&mut self, data: &str) -> Result<bool> {
+        dispatch!(self, context => context.clear_if_unchanged(data))
+    }
+
+    fn get_content(&mut self, mime_type: &str) -> Result<ClipboardContent> {
+        dispatch!(self, context => context.get_content(mime_type))
+    }
+
+    fn get_bytes(&mut self, mime_type: &str) -> Result<Vec<u8>> {
+        dispatch!(self, context => context.get_bytes(mime_type))
+    }
+
+    fn set_bytes(&mut self, mime_type: &str, data: Vec<u8>) -> Result<()> {
+        dispatch!(self, context => context.set_bytes(mime_type, data))
+    }
+
+    fn paste_to_writer(&mut self, mime_type: &str, writer: &mut dyn Write) -> Result<u64> {
+        dispatch!(self, context => context.paste_to_writer(mime_type, writer))
+    }
+
+    fn copy_from_reader(&mut self, mime_type: &str, reader: &mut dyn Read) -> Result<()> {
+        dispatch!(self, context => context.copy_from_reader(mime_type, reader))
+    }
+
+    fn set_content(&mut self, content: ClipboardContent) -> Result<()> {
+        dispatch!(self, context => context.set_content(content))
+    }
+
+    fn set_content_multi(&mut self, contents: Vec<ClipboardContent>) -> Result<()> {
+        dispatch!(self, context => context.set_content_multi(contents))
+    }
+
+    fn set_sensitive_contents(&mut self, data: String) -> Result<()> {
+        dispatch!(self, context => context.set_sensitive_contents(data))
+    }
+
+    fn available_formats(&mut self) -> Result<Vec<String>> {
+        dispatch!(self, context => context.available_formats())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_backend() {
+        assert_eq!("wayland".parse::<Backend>().unwrap(), Backend::Wayland);
+        assert_eq!("X11".parse::<Backend>().unwrap(), Backend::X11);
+        assert!(matches!(
+            "quartz".parse::<Backend>(),
+            Err(ClipboardError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn fallback_order() {
+        if env::var_os(BACKEND_ENV_VAR).is_some() {
+            return;
         }
+        let builder = LinuxClipboardContext::builder()
+            .prefer(Backend::X11)
+            .fallback_to(Backend::Osc52);
+        assert_eq!(
+            builder.order().unwrap(),
+            vec![Backend::X11, Backend::Wayland, Backend::Osc52]
+        );
+        assert_eq!(builder.fallback(false).order().unwrap(), vec![Backend::X11]);
     }
 }
@@ -15,7 +15,7 @@ limitations under the License.
 */
 
 use crate::common::*;
-use anyhow::{anyhow, Result};
+use crate::error::{ClipboardError, Result};
 use objc::runtime::{Class, Object};
 use objc_foundation::{INSArray, INSObject, INSString};
 use objc_foundation::{NSArray, NSDictionary, NSObject, NSString};
@@ -32,11 +32,13 @@ extern "C" {}
 
 impl ClipboardProvider for MacOSClipboardContext {
     fn new() -> Result<MacOSClipboardContext> {
-        let cls =
-            Class::get("NSPasteboard").ok_or_else(|| anyhow!("Class::get(\"NSPasteboard\")"))?;
+        let cls = Class::get("NSPasteboard")
+            .ok_or_else(|| backend_error("Class::get(\"NSPasteboard\")"))?;
         let pasteboard: *mut Object = unsafe { msg_send![cls, generalPasteboard] };
         if pasteboard.is_null() {
-            return Err(anyhow!("NSPasteboard#generalPasteboard returned null"));
+            return Err(backend_error(
+                "NSPasteboard#generalPasteboard returned null",
+            ));
         }
         let pasteboard: Id<Object> = unsafe { Id::from_ptr(pasteboard) };
         Ok(MacOSClipboardContext { pasteboard })
@@ -53,16 +55,14 @@ impl ClipboardProvider for MacOSClipboardContext {
             let obj: *mut NSArray<NSString> =
                 msg_send![self.pasteboard, readObjectsForClasses:&*classes options:&*options];
             if obj.is_null() {
-                return Err(anyhow!(
+                return Err(backend_error(
                     "pasteboard#readObjectsForClasses:options: returned null",
                 ));
             }
             Id::from_ptr(obj)
         };
         if string_array.count() == 0 {
-            Err(anyhow!(
-                "pasteboard#readObjectsForClasses:options: returned empty",
-            ))
+            Err(ClipboardError::Empty)
         } else {
             Ok(string_array[0].as_str().to_owned())
         }
@@ -75,7 +75,7 @@ impl ClipboardProvider for MacOSClipboardContext {
         if success {
             Ok(())
         } else {
-            Err(anyhow!("NSPasteboard#writeObjects: returned false"))
+            Err(backend_error("NSPasteboard#writeObjects: returned false"))
         }
     }
 
@@ -85,6 +85,10 @@ impl ClipboardProvider for MacOSClipboardContext {
     }
 }
 
+fn backend_error(message: &str) -> ClipboardError {
+    ClipboardError::Backend(message.into())
+}
+
 // this is a convenience function that both cocoa-rs and
 //  glutin define, which seems to depend on the fact that
 //  Option::None has the same representation as a null pointer
@@ -0,0 +1,294 @@
+//! An in-memory clipboard for tests.
+//!
+//! Enabled with the `mock` cargo feature.
+
+use crate::common::*;
+use crate::error::{ClipboardError, Result};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+#[derive(Default)]
+struct State {
+    selections: HashMap<ClipboardKind, Vec<ClipboardContent>>,
+    failures: VecDeque<ClipboardError>,
+}
+
+/// Clipboard state shared by any number of `MemoryClipboardContext`s.
+///
+/// Cloning a `MemoryClipboard` yields a handle to the same state.
+#[derive(Clone, Default)]
+pub struct MemoryClipboard {
+    state: Arc<Mutex<State>>,
+}
+
+impl MemoryClipboard {
+    /// Creates empty clipboard state, separate from any other.
+    pub fn new() -> MemoryClipboard {
+        MemoryClipboard::default()
+    }
+
+    /// The state shared by every context created with
+    /// `MemoryClipboardContext::new`.
+    pub fn global() -> MemoryClipboard {
+        static GLOBAL: Mutex<Option<MemoryClipboard>> = Mutex::new(None);
+        GLOBAL
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get_or_insert_with(MemoryClipboard::new)
+            .clone()
+    }
+
+    /// Makes the next operation on any context using this state fail
+    /// with `err`. Queued failures are returned in order, one per
+    /// operation.
+    pub fn fail_next(&self, err: ClipboardError) {
+        self.lock().failures.push_back(err);
+    }
+
+    /// Sets the representations held by `kind` without going through a
+    /// context, e.g. to simulate another application copying.
+    pub fn set(&self, kind: ClipboardKind, contents: Vec<ClipboardContent>) {
+        self.lock().selections.insert(kind, contents);
+    }
+
+    /// The representations held by `kind`, empty if nothing is copied.
+    pub fn get(&self, kind: ClipboardKind) -> Vec<ClipboardContent> {
+        self.lock()
+            .selections
+            .get(&kind)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A clipboard that lives in process memory, for testing code that uses
+/// a `ClipboardProvider` without a display server.
+///
+/// Contexts created with `new` share global state, like the contexts of
+/// a real clipboard do. Use `with_clipboard` for state that is isolated
+/// from other tests.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(feature = "mock")]
+/// # {
+/// use cli_clipboard::memory_clipboard::{MemoryClipboard, MemoryClipboardContext};
+/// use cli_clipboard::{ClipboardError, ClipboardProvider};
+///
+/// let clipboard = MemoryClipboard::new();
+/// let mut ctx = MemoryClipboardContext::with_clipboard(clipboard.clone());
+/// ctx.set_contents("foo bar baz".to_string()).unwrap();
+/// assert_eq!(ctx.get_contents().unwrap(), "foo bar baz");
+///
+/// clipboard.fail_next(ClipboardError::Timeout);
+/// assert!(ctx.get_contents().is_err());
+/// # }
+/// ```
+pub struct MemoryClipboardContext {
+    clipboard: MemoryClipboard,
+    kind: ClipboardKind,
+}
+
+impl MemoryClipboardContext {
+    /// Creates a context for the regular clipboard of `clipboard`.
+    pub fn with_clipboard(clipboard: MemoryClipboard) -> MemoryClipboardContext {
+        MemoryClipboardContext {
+            clipboard,
+            kind: ClipboardKind::Clipboard,
+        }
+    }
+
+    /// Switches the selection this context operates on.
+    pub fn with_kind(mut self, kind: ClipboardKind) -> MemoryClipboardContext {
+        self.kind = kind;
+        self
+    }
+
+    /// The state this context operates on.
+    pub fn clipboard(&self) -> &MemoryClipboard {
+        &self.clipboard
+    }
+
+    /// Returns the next injected failure, if any.
+    fn check(&self) -> Result<()> {
+        match self.clipboard.lock().failures.pop_front() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    /// The selections this context operates on, in the order they are
+    /// read from.
+    fn kinds(&self) -> &'static [ClipboardKind] {
+        match self.kind {
+            ClipboardKind::Clipboard => &[ClipboardKind::Clipboard],
+            ClipboardKind::Primary => &[ClipboardKind::Primary],
+            ClipboardKind::Secondary => &[ClipboardKind::Secondary],
+            ClipboardKind::Both => &[ClipboardKind::Clipboard, ClipboardKind::Primary],
+        }
+    }
+
+    fn load(&self) -> Result<Vec<ClipboardContent>> {
+        self.check()?;
+        self.kinds()
+            .iter()
+            .map(|&kind| self.clipboard.get(kind))
+            .find(|contents| !contents.is_empty())
+            .ok_or(ClipboardError::Empty)
+    }
+
+    fn store(&self, contents: Vec<ClipboardContent>) -> Result<()> {
+        self.check()?;
+        for &kind in self.kinds() {
+            self.clipboard.set(kind, contents.clone());
+        }
+        Ok(())
+    }
+}
+
+impl ClipboardProvider for MemoryClipboardContext {
+    /// Creates a context for the regular clipboard of the global state.
+    fn new() -> Result<MemoryClipboardContext> {
+        Ok(MemoryClipboardContext::with_clipboard(
+            MemoryClipboard::global(),
+        ))
+    }
+
+    fn get_contents(&mut self) -> Result<String> {
+        Ok(String::from_utf8(self.get_bytes("text/plain")?)?)
+    }
+
+    fn set_contents(&mut self, data: String) -> Result<()> {
+        self.store(vec![ClipboardContent::Text(data)])
+    }
+
+    fn clear(&mut self) -> Result<()> {
+        self.store(Vec::new())
+    }
+
+    fn get_content(&mut self, mime_type: &str) -> Result<ClipboardContent> {
+        ClipboardContent::from_bytes(mime_type, self.get_bytes(mime_type)?)
+    }
+
+    fn get_bytes(&mut self, mime_type: &str) -> Result<Vec<u8>> {
+        self.load()?
+            .into_iter()
+            .find(|content| {
+                content.mime_type() == mime_type
+                    || is_plain_text(mime_type) && is_plain_text(content.mime_type())
+            })
+            .map(ClipboardContent::into_bytes)
+            .ok_or_else(|| ClipboardError::FormatUnavailable(mime_type.to_owned()))
+    }
+
+    fn set_bytes(&mut self, mime_type: &str, data: Vec<u8>) -> Result<()> {
+        self.store(vec![ClipboardContent::Other {
+            mime_type: mime_type.to_owned(),
+            data,
+        }])
+    }
+
+    fn set_content(&mut self, content: ClipboardContent) -> Result<()> {
+        self.store(vec![content])
+    }
+
+    fn set_content_multi(&mut self, contents: Vec<ClipboardContent>) -> Result<()> {
+        if contents.is_empty() {
+            return Err(no_representations());
+        }
+        self.store(contents)
+    }
+
+    /// Lists the MIME types of the stored representations, in the order
+    /// they were copied.
+    fn available_formats(&mut self) -> Result<Vec<String>> {
+        match self.load() {
+            Ok(contents) => Ok(contents
+                .iter()
+                .map(|content| content.mime_type().to_owned())
+                .collect()),
+            Err(ClipboardError::Empty) => Ok(Vec::new()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selections_are_separate() {
+        let clipboard = MemoryClipboard::new();
+        let mut regular = MemoryClipboardContext::with_clipboard(clipboard.clone());
+        let mut primary = MemoryClipboardContext::with_clipboard(clipboard.clone())
+            .with_kind(ClipboardKind::Primary);
+
+        regular.set_contents("regular".to_owned()).unwrap();
+        assert!(matches!(primary.get_contents(), Err(ClipboardError::Empty)));
+
+        primary.set_contents("primary".to_owned()).unwrap();
+        assert_eq!(regular.get_contents().unwrap(), "regular");
+        assert_eq!(primary.get_contents().unwrap(), "primary");
+
+        regular.clear().unwrap();
+        assert_eq!(regular.get_contents_opt().unwrap(), None);
+        assert_eq!(regular.available_formats().unwrap(), Vec::<String>::new());
+
+        let mut both = MemoryClipboardContext::with_clipboard(clipboard.clone())
+            .with_kind(ClipboardKind::Both);
+        assert_eq!(both.get_contents().unwrap(), "primary");
+        both.set_contents("both".to_owned()).unwrap();
+        assert_eq!(regular.get_contents().unwrap(), "both");
+        assert_eq!(primary.get_contents().unwrap(), "both");
+        both.clear().unwrap();
+        assert_eq!(primary.get_contents_opt().unwrap(), None);
+    }
+
+    #[test]
+    fn representations_and_failures() {
+        let clipboard = MemoryClipboard::new();
+        let mut ctx = MemoryClipboardContext::with_clipboard(clipboard.clone());
+
+        ctx.set_content_multi(vec![
+            ClipboardContent::Html("<b>bold</b>".to_owned()),
+            ClipboardContent::Text("bold".to_owned()),
+        ])
+        .unwrap();
+        assert_eq!(
+            ctx.available_formats().unwrap(),
+            vec!["text/html", "text/plain;charset=utf-8"]
+        );
+        assert_eq!(ctx.get_contents().unwrap(), "bold");
+        assert!(matches!(
+            ctx.get_content("image/png"),
+            Err(ClipboardError::FormatUnavailable(_))
+        ));
+
+        ctx.set_bytes("text/plain", b"caf\xe9".to_vec()).unwrap();
+        assert_eq!(ctx.get_bytes("text/plain").unwrap(), b"caf\xe9");
+        assert!(matches!(
+            ctx.get_contents(),
+            Err(ClipboardError::InvalidUtf8(_))
+        ));
+        assert_eq!(ctx.get_contents_lossy().unwrap(), "caf\u{fffd}");
+
+        let mut png: &[u8] = &[0x89, b'P', b'N', b'G'];
+        ctx.copy_from_reader("image/png", &mut png).unwrap();
+        let mut pasted = Vec::new();
+        assert_eq!(ctx.paste_to_writer("image/png", &mut pasted).unwrap(), 4);
+        assert_eq!(pasted, [0x89, b'P', b'N', b'G']);
+        ctx.set_contents("bold".to_owned()).unwrap();
+
+        clipboard.fail_next(ClipboardError::Timeout);
+        clipboard.fail_next(ClipboardError::Empty);
+        assert!(matches!(ctx.get_contents(), Err(ClipboardError::Timeout)));
+        assert!(matches!(ctx.get_contents(), Err(ClipboardError::Empty)));
+        assert_eq!(ctx.get_contents().unwrap(), "bold");
+    }
+}
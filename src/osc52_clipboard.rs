@@ -0,0 +1,294 @@
+use crate::common::*;
+use crate::error::{ClipboardError, Result};
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::time::{Duration, Instant};
+
+const TTY_PATH: &str = "/dev/tty";
+
+// GNU screen keeps at most 768 bytes of a DCS string, including a
+// terminating NUL, so sequences are split into pieces of this size.
+const SCREEN_CHUNK_SIZE: usize = 767;
+
+/// How escape sequences are passed through a terminal multiplexer to the
+/// outer terminal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Passthrough {
+    /// Write sequences as they are
+    None,
+    /// Wrap sequences in tmux's DCS passthrough. Requires
+    /// `set -g allow-passthrough on` with tmux 3.3 and later.
+    Tmux,
+    /// Wrap sequences in DCS strings for GNU screen
+    Screen,
+}
+
+impl Passthrough {
+    /// Detects tmux through `TMUX` and GNU screen through `STY`.
+    pub fn detect() -> Passthrough {
+        if env::var_os("TMUX").is_some() {
+            Passthrough::Tmux
+        } else if env::var_os("STY").is_some() {
+            Passthrough::Screen
+        } else {
+            Passthrough::None
+        }
+    }
+
+    /// Wraps `sequence` so that it reaches the outer terminal.
+    fn wrap(self, sequence: &str) -> String {
+        match self {
+            Passthrough::None => sequence.to_owned(),
+            Passthrough::Tmux => {
+                format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"))
+            }
+            Passthrough::Screen => sequence
+                .as_bytes()
+                .chunks(SCREEN_CHUNK_SIZE)
+                .map(|chunk| format!("\x1bP{}\x1b\\", String::from_utf8_lossy(chunk)))
+                .collect(),
+        }
+    }
+}
+
+/// Interface to the clipboard of the terminal emulator, using the OSC 52
+/// escape sequence.
+///
+/// This works wherever the terminal supports OSC 52, including over SSH
+/// where neither Wayland nor X11 can be reached. The sequences are
+/// written to the controlling terminal, so copying works even if stdout
+/// is redirected.
+///
+/// Most terminals do not allow applications to read the clipboard, so
+/// `get_contents` returns Err unless querying has been enabled with
+/// `with_query_timeout`.
+///
+/// Inside tmux or GNU screen the sequences are wrapped so that the
+/// multiplexer passes them on to the outer terminal, see `Passthrough`.
+///
+/// # Example
+///
+/// ```noop
+/// use cli_clipboard::ClipboardProvider;
+/// use cli_clipboard::osc52_clipboard::Osc52ClipboardContext;
+///
+/// let mut clipboard = Osc52ClipboardContext::new().unwrap();
+/// clipboard.set_contents("foo bar baz".to_string()).unwrap();
+/// ```
+pub struct Osc52ClipboardContext {
+    tty: File,
+    query_timeout: Option<Duration>,
+    passthrough: Passthrough,
+    kind: ClipboardKind,
+}
+
+impl Osc52ClipboardContext {
+    /// Enables reading the clipboard by querying the terminal, waiting
+    /// at most `timeout` for its reply.
+    pub fn with_query_timeout(mut self, timeout: Duration) -> Osc52ClipboardContext {
+        self.query_timeout = Some(timeout);
+        self
+    }
+
+    /// Switches the selection this context operates on. Defaults to
+    /// `ClipboardKind::Clipboard`.
+    pub fn with_kind(mut self, kind: ClipboardKind) -> Osc52ClipboardContext {
+        self.kind = kind;
+        self
+    }
+
+    /// Overrides the multiplexer passthrough detected by `new`.
+    pub fn with_passthrough(mut self, passthrough: Passthrough) -> Osc52ClipboardContext {
+        self.passthrough = passthrough;
+        self
+    }
+
+    fn write_sequence(&mut self, payload: &str) -> Result<()> {
+        let sequence = self.passthrough.wrap(&osc52(selection(self.kind), payload));
+        self.tty.write_all(sequence.as_bytes())?;
+        self.tty.flush()?;
+        Ok(())
+    }
+
+    /// Asks the terminal for the clipboard and reads the base64 encoded
+    /// reply.
+    fn query(&mut self, timeout: Duration) -> Result<Vec<u8>> {
+        let _raw_mode = RawMode::enable(&self.tty)?;
+        self.write_sequence("?")?;
+
+        let deadline = Instant::now() + timeout;
+        let mut reply = Vec::new();
+        let mut buf = [0; 1024];
+        loop {
+            if let Some(data) = parse_reply(&reply) {
+                return base64::decode(data).map_err(|e| ClipboardError::Backend(Box::new(e)));
+            }
+
+            let now = Instant::now();
+            if now >= deadline || !wait_readable(&self.tty, deadline - now)? {
+                return Err(ClipboardError::Timeout);
+            }
+            let read = self.tty.read(&mut buf)?;
+            reply.extend_from_slice(&buf[..read]);
+        }
+    }
+}
+
+impl ClipboardProvider for Osc52ClipboardContext {
+    /// Opens the controlling terminal, returning Err if the process has
+    /// none. The multiplexer passthrough is detected from the
+    /// environment.
+    fn new() -> Result<Osc52ClipboardContext> {
+        let tty = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(TTY_PATH)
+            .map_err(|e| ClipboardError::NoDisplayServer(Box::new(e)))?;
+        Ok(Osc52ClipboardContext {
+            tty,
+            query_timeout: None,
+            kind: ClipboardKind::Clipboard,
+            passthrough: Passthrough::detect(),
+        })
+    }
+
+    /// Queries the terminal for the clipboard contents.
+    ///
+    /// Returns `ClipboardError::Unsupported` unless querying has been
+    /// enabled and `ClipboardError::Timeout` if the terminal does not
+    /// reply in time, which is what most terminals do when reading the
+    /// clipboard is disabled.
+    fn get_contents(&mut self) -> Result<String> {
+        let timeout = self.query_timeout.ok_or_else(|| {
+            ClipboardError::Unsupported("reading the clipboard over OSC 52".to_owned())
+        })?;
+        let data = self.query(timeout)?;
+        if data.is_empty() {
+            return Err(ClipboardError::Empty);
+        }
+        Ok(String::from_utf8(data)?)
+    }
+
+    fn set_contents(&mut self, data: String) -> Result<()> {
+        self.write_sequence(&base64::encode(data))
+    }
+
+    /// Clears the clipboard by sending data that is not valid base64,
+    /// which terminals treat as a request to clear the selection.
+    fn clear(&mut self) -> Result<()> {
+        self.write_sequence("!")
+    }
+}
+
+/// The OSC 52 selection parameter for `kind`.
+fn selection(kind: ClipboardKind) -> &'static str {
+    match kind {
+        ClipboardKind::Clipboard => "c",
+        ClipboardKind::Primary => "p",
+        ClipboardKind::Secondary => "s",
+        ClipboardKind::Both => "cp",
+    }
+}
+
+/// Builds an OSC 52 sequence for `selection`, terminated by BEL.
+fn osc52(selection: &str, payload: &str) -> String {
+    format!("\x1b]52;{};{}\x07", selection, payload)
+}
+
+/// Extracts the base64 data from a complete OSC 52 reply, which is
+/// terminated by either BEL or ST.
+fn parse_reply(reply: &[u8]) -> Option<&[u8]> {
+    let start = find(reply, b"\x1b]52;")? + 5;
+    let reply = &reply[start..];
+    let data = &reply[find(reply, b";")? + 1..];
+    let end = data
+        .iter()
+        .position(|&b| b == b'\x07')
+        .or_else(|| find(data, b"\x1b\\"))?;
+    Some(&data[..end])
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Waits until `tty` is readable, returning false on timeout.
+fn wait_readable(tty: &File, timeout: Duration) -> Result<bool> {
+    let mut fd = libc::pollfd {
+        fd: tty.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let timeout = timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+    match unsafe { libc::poll(&mut fd, 1, timeout) } {
+        -1 => Err(io::Error::last_os_error().into()),
+        0 => Ok(false),
+        _ => Ok(true),
+    }
+}
+
+/// Disables line buffering and echo on the terminal while alive, so that
+/// the reply to a query can be read without the user pressing enter.
+struct RawMode {
+    fd: RawFd,
+    original: libc::termios,
+}
+
+impl RawMode {
+    fn enable(tty: &File) -> Result<RawMode> {
+        let fd = tty.as_raw_fd();
+        let mut original = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(fd, &mut original) } == -1 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        let mut raw = original;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+        if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &raw) } == -1 {
+            return Err(io::Error::last_os_error().into());
+        }
+        Ok(RawMode { fd, original })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe { libc::tcsetattr(self.fd, libc::TCSANOW, &self.original) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_replies() {
+        let encoded = base64::encode("foo bar baz");
+        assert_eq!(osc52("c", &encoded), format!("\x1b]52;c;{}\x07", encoded));
+
+        let bel = format!("\x1b]52;c;{}\x07", encoded);
+        let st = format!("\x1b]52;c;{}\x1b\\", encoded);
+        assert_eq!(parse_reply(bel.as_bytes()), Some(encoded.as_bytes()));
+        assert_eq!(parse_reply(st.as_bytes()), Some(encoded.as_bytes()));
+        assert_eq!(parse_reply(&st.as_bytes()[..st.len() - 2]), None);
+    }
+
+    #[test]
+    fn passthrough_wrapping() {
+        let sequence = osc52("c", "Zm9v");
+        assert_eq!(Passthrough::None.wrap(&sequence), sequence);
+        assert_eq!(
+            Passthrough::Tmux.wrap(&sequence),
+            "\x1bPtmux;\x1b\x1b]52;c;Zm9v\x07\x1b\\"
+        );
+
+        let long = osc52("c", &"A".repeat(1000));
+        let wrapped = Passthrough::Screen.wrap(&long);
+        assert_eq!(wrapped.matches("\x1bP").count(), 2);
+        assert_eq!(wrapped.replace("\x1bP", "").replace("\x1b\\", ""), long);
+    }
+}
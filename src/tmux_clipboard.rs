@@ -0,0 +1,71 @@
+use crate::command_clipboard::run;
+use crate::common::*;
+use crate::error::{ClipboardError, Result};
+use std::env;
+
+/// Interface to the paste buffer of the surrounding tmux session.
+///
+/// Copying uses `tmux load-buffer -w -`, which also asks tmux to set the
+/// clipboard of the outer terminal through OSC 52 (tmux 3.2 and later,
+/// subject to tmux's `set-clipboard` option). Pasting reads the most
+/// recent buffer with `tmux save-buffer -`, so text copied in copy mode
+/// by anyone attached to the session can be pasted.
+///
+/// # Example
+///
+/// ```noop
+/// use cli_clipboard::ClipboardProvider;
+/// use cli_clipboard::tmux_clipboard::TmuxClipboardContext;
+///
+/// let mut clipboard = TmuxClipboardContext::new().unwrap();
+/// clipboard.set_contents("foo bar baz".to_string()).unwrap();
+/// assert_eq!(clipboard.get_contents().unwrap(), "foo bar baz");
+/// ```
+pub struct TmuxClipboardContext;
+
+impl ClipboardProvider for TmuxClipboardContext {
+    /// Returns Err unless running inside tmux, as indicated by `TMUX`.
+    fn new() -> Result<TmuxClipboardContext> {
+        if env::var_os("TMUX").is_none() {
+            return Err(ClipboardError::NoDisplayServer("TMUX is not set".into()));
+        }
+        Ok(TmuxClipboardContext)
+    }
+
+    /// Returns `ClipboardError::Empty` if the session has no buffers.
+    fn get_contents(&mut self) -> Result<String> {
+        Ok(String::from_utf8(self.get_bytes("text/plain")?)?)
+    }
+
+    /// Returns the raw contents of the most recent buffer. Only plain
+    /// text is supported.
+    fn get_bytes(&mut self, mime_type: &str) -> Result<Vec<u8>> {
+        if !is_plain_text(mime_type) {
+            return Err(ClipboardError::Unsupported(format!(
+                "MIME type {}",
+                mime_type
+            )));
+        }
+        tmux(&["save-buffer", "-"], None)
+    }
+
+    fn set_contents(&mut self, data: String) -> Result<()> {
+        tmux(&["load-buffer", "-w", "-"], Some(data.as_bytes())).map(drop)
+    }
+
+    /// Deletes the most recent buffer.
+    fn clear(&mut self) -> Result<()> {
+        match tmux(&["delete-buffer"], None) {
+            Ok(_) | Err(ClipboardError::Empty) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+fn tmux(args: &[&str], input: Option<&[u8]>) -> Result<Vec<u8>> {
+    let command: Vec<&str> = Some("tmux")
+        .into_iter()
+        .chain(args.iter().copied())
+        .collect();
+    run(&command, input)
+}
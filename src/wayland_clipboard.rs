@@ -15,13 +15,39 @@ limitations under the License.
 */
 
 use crate::common::*;
-use anyhow::Result;
-use failure::Fail;
-use std::io::{self, Read};
+use crate::error::{ClipboardError, Result};
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::io::FromRawFd;
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use wayland_client::protocol::wl_seat::WlSeat;
+use wayland_client::{global_filter, Display, EventQueue, GlobalManager, Interface, Main};
+use wayland_protocols::wlr::unstable::data_control::v1::client::{
+    zwlr_data_control_device_v1::{self, ZwlrDataControlDeviceV1},
+    zwlr_data_control_manager_v1::ZwlrDataControlManagerV1,
+    zwlr_data_control_offer_v1::{self, ZwlrDataControlOfferV1},
+    zwlr_data_control_source_v1::{self, ZwlrDataControlSourceV1},
+};
 use wl_clipboard_rs::{
     copy::{self, clear, Options, ServeRequests},
     paste, utils,
 };
+use zeroize::{Zeroize, Zeroizing};
+
+// The MIME types wl-clipboard-rs offers text under, which sensitive
+// copies are offered under as well.
+const TEXT_MIME_TYPES: [&str; 5] = [
+    "text/plain;charset=utf-8",
+    "text/plain",
+    "STRING",
+    "UTF8_STRING",
+    "TEXT",
+];
 
 /// Interface to the clipboard for Wayland windowing systems.
 ///
@@ -31,8 +57,10 @@ use wl_clipboard_rs::{
 /// remain after your application shuts down, consider daemonizing the
 /// clipboard components of your application.
 ///
-/// `WaylandClipboardContext` automatically detects support for and
-/// uses the primary selection protocol.
+/// `WaylandClipboardContext` operates on the regular clipboard, like
+/// the X11 provider does by default. Use `set_kind` to operate on the
+/// primary selection or on both, and `get_primary_contents` to read the
+/// primary selection once.
 ///
 /// # Example
 ///
@@ -46,6 +74,53 @@ use wl_clipboard_rs::{
 /// ```
 pub struct WaylandClipboardContext {
     supports_primary_selection: bool,
+    kind: ClipboardKind,
+}
+
+impl WaylandClipboardContext {
+    /// Switches the selection this context operates on, with the same
+    /// meaning as on X11. Defaults to `ClipboardKind::Clipboard`.
+    ///
+    /// `ClipboardKind::Primary` fails on use if the compositor does not
+    /// support the primary selection, and `ClipboardKind::Both` then
+    /// only uses the regular clipboard. `ClipboardKind::Secondary` is
+    /// not available on Wayland.
+    pub fn set_kind(&mut self, kind: ClipboardKind) {
+        self.kind = kind;
+    }
+
+    /// Like `set_kind`, for use right after construction.
+    pub fn with_kind(mut self, kind: ClipboardKind) -> WaylandClipboardContext {
+        self.set_kind(kind);
+        self
+    }
+
+    /// Pastes text from the regular clipboard, regardless of the
+    /// selection this context operates on.
+    pub fn get_clipboard_contents(&self) -> Result<String> {
+        self.get_contents_from(ClipboardKind::Clipboard)
+    }
+
+    /// Pastes text from the primary selection, regardless of the
+    /// selection this context operates on.
+    ///
+    /// Returns `ClipboardError::UnsupportedProtocol` if the compositor
+    /// does not support the primary selection.
+    pub fn get_primary_contents(&self) -> Result<String> {
+        self.get_contents_from(ClipboardKind::Primary)
+    }
+
+    /// Pastes text from the selection `kind`, regardless of the
+    /// selection this context operates on.
+    pub fn get_contents_from(&self, kind: ClipboardKind) -> Result<String> {
+        read_into_string(&mut self.get_reader(kind, paste::MimeType::Text)?)
+    }
+
+    /// Whether the compositor supported the primary selection when this
+    /// context was constructed.
+    pub fn supports_primary_selection(&self) -> bool {
+        self.supports_primary_selection
+    }
 }
 
 impl ClipboardProvider for WaylandClipboardContext {
@@ -64,111 +139,570 @@ impl ClipboardProvider for WaylandClipboardContext {
             Ok(v) => v,
             Err(e) => match e {
                 utils::PrimarySelectionCheckError::NoSeats => false,
-                _ => return Err(into_boxed_error(e)),
+                _ => return Err(e.into()),
             },
         };
 
         Ok(WaylandClipboardContext {
             supports_primary_selection,
+            kind: ClipboardKind::Clipboard,
         })
     }
 
-    /// Pastes from the Wayland clipboard.
-    ///
-    /// If the Wayland environment supported the primary selection when
-    /// this context was constructed, first checks the primary
-    /// selection. If pasting from the primary selection raises an
-    /// error or the primary selection is unsupported, falls back to
-    /// the regular clipboard.
+    /// Pastes from the selection this context operates on, the regular
+    /// clipboard unless changed with `set_kind`.
     ///
-    /// An empty clipboard is not considered an error, but the
+    /// Returns `ClipboardError::Empty` if nothing has been copied. The
     /// clipboard must indicate a text MIME type and the contained text
     /// must be valid UTF-8.
     fn get_contents(&mut self) -> Result<String> {
-        if self.supports_primary_selection {
-            match paste::get_contents(
-                paste::ClipboardType::Primary,
-                paste::Seat::Unspecified,
-                paste::MimeType::Text,
-            ) {
-                Ok((mut reader, _)) => {
-                    // this looks weird, but rustc won't let me do it
-                    // the natural way
-                    return Ok(read_into_string(&mut reader).map_err(Box::new)?);
-                }
-                Err(e) => match e {
-                    paste::Error::NoSeats
-                    | paste::Error::ClipboardEmpty
-                    | paste::Error::NoMimeType => return Ok("".to_string()),
-                    _ => (),
-                },
+        self.get_contents_from(self.kind)
+    }
+
+    /// Copies to the selection this context operates on, the regular
+    /// clipboard unless changed with `set_kind`.
+    fn set_contents(&mut self, data: String) -> Result<()> {
+        self.copy_options()?
+            .copy(
+                copy::Source::Bytes(data.into_bytes().into()),
+                copy::MimeType::Text,
+            )
+            .map_err(ClipboardError::from)
+    }
+
+    /// Pastes `mime_type` contents from the Wayland clipboard.
+    ///
+    /// Reads the same selection as `get_contents`, but returns Err if
+    /// the clipboard is empty or does not offer `mime_type`.
+    fn get_content(&mut self, mime_type: &str) -> Result<ClipboardContent> {
+        ClipboardContent::from_bytes(mime_type, self.get_bytes(mime_type)?)
+    }
+
+    /// Pastes the raw `mime_type` contents from the Wayland clipboard.
+    ///
+    /// Follows the same rules as `get_content`.
+    fn get_bytes(&mut self, mime_type: &str) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        self.paste_to_writer(mime_type, &mut data)?;
+        Ok(data)
+    }
+
+    /// Streams the `mime_type` contents from the pipe the Wayland
+    /// clipboard owner writes to into `writer`.
+    ///
+    /// Follows the same rules as `get_content`.
+    fn paste_to_writer(&mut self, mime_type: &str, writer: &mut dyn Write) -> Result<u64> {
+        let request = if is_plain_text(mime_type) {
+            paste::MimeType::Text
+        } else {
+            paste::MimeType::Specific(mime_type)
+        };
+        let mut reader = self.get_reader(self.kind, request).map_err(|e| match e {
+            ClipboardError::FormatUnavailable(_) => {
+                ClipboardError::FormatUnavailable(mime_type.to_owned())
             }
+            e => e,
+        })?;
+
+        Ok(io::copy(&mut reader, writer)?)
+    }
+
+    /// Copies raw bytes to the Wayland clipboard under `mime_type`.
+    ///
+    /// Plain text MIME types are offered under all the text MIME types
+    /// `set_contents` uses.
+    fn set_bytes(&mut self, mime_type: &str, data: Vec<u8>) -> Result<()> {
+        let mime_type = if is_plain_text(mime_type) {
+            copy::MimeType::Text
+        } else {
+            copy::MimeType::Specific(mime_type.to_owned())
+        };
+
+        self.copy_options()?
+            .copy(copy::Source::Bytes(data.into()), mime_type)
+            .map_err(ClipboardError::from)
+    }
+
+    /// Copies `content` to the Wayland clipboard under its MIME type.
+    ///
+    /// Copies to the same selections as `set_contents`.
+    fn set_content(&mut self, content: ClipboardContent) -> Result<()> {
+        let mime_type = match content {
+            ClipboardContent::Text(_) => copy::MimeType::Text,
+            _ => copy::MimeType::Specific(content.mime_type().to_owned()),
+        };
+
+        self.copy_options()?
+            .copy(copy::Source::Bytes(content.into_bytes().into()), mime_type)
+            .map_err(ClipboardError::from)
+    }
+
+    /// Copies all `contents` to the Wayland clipboard in a single
+    /// offer, registering the MIME type of each representation.
+    ///
+    /// Plain text representations are registered first so that the
+    /// additional plain text MIME types wl-clipboard-rs offers are
+    /// backed by them rather than by e.g. HTML.
+    fn set_content_multi(&mut self, mut contents: Vec<ClipboardContent>) -> Result<()> {
+        if contents.is_empty() {
+            return Err(no_representations());
         }
+        contents.sort_by_key(|content| match content {
+            ClipboardContent::Text(_) => 0,
+            _ => 1,
+        });
 
-        let mut reader = match paste::get_contents(
-            paste::ClipboardType::Regular,
-            paste::Seat::Unspecified,
-            paste::MimeType::Text,
-        ) {
-            Ok((reader, _)) => reader,
-            Err(e) => match e {
-                paste::Error::NoSeats | paste::Error::ClipboardEmpty | paste::Error::NoMimeType => {
-                    return Ok("".to_string());
-                }
-                _ => return Err(into_boxed_error(e)),
-            },
+        let sources = contents
+            .into_iter()
+            .map(|content| copy::MimeSource {
+                mime_type: match content {
+                    ClipboardContent::Text(_) => copy::MimeType::Text,
+                    _ => copy::MimeType::Specific(content.mime_type().to_owned()),
+                },
+                source: copy::Source::Bytes(content.into_bytes().into()),
+            })
+            .collect();
+
+        self.copy_options()?
+            .copy_multi(sources)
+            .map_err(ClipboardError::from)
+    }
+
+    /// Copies `data` along with the `x-kde-passwordManagerHint` MIME type.
+    ///
+    /// Unlike the other copies, which wl-clipboard-rs serves from a
+    /// temporary file, `data` is served from memory on a background
+    /// thread and overwritten with zeros once every seat's selection
+    /// has been replaced.
+    fn set_sensitive_contents(&mut self, data: String) -> Result<()> {
+        let target = self.copy_target()?;
+        let data = Zeroizing::new(data.into_bytes());
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || match offer_sensitive(target, data) {
+            Ok(offer) => {
+                let _ = sender.send(Ok(()));
+                offer.run();
+            }
+            Err(e) => {
+                let _ = sender.send(Err(e));
+            }
+        });
+
+        receiver.recv().unwrap_or_else(|_| {
+            Err(ClipboardError::Backend(
+                "the Wayland serving thread panicked".into(),
+            ))
+        })
+    }
+
+    /// Lists the MIME types offered on the Wayland clipboard, sorted by
+    /// name.
+    ///
+    /// Reads the same selection as `get_contents`. An empty clipboard
+    /// offers no MIME types.
+    fn available_formats(&mut self) -> Result<Vec<String>> {
+        let mime_types = self.paste(self.kind, |clipboard| {
+            paste::get_mime_types(clipboard, paste::Seat::Unspecified)
+        });
+        let mut mime_types: Vec<String> = match mime_types {
+            Ok(mime_types) => mime_types.into_iter().collect(),
+            Err(ClipboardError::Empty) => Vec::new(),
+            Err(e) => return Err(e),
         };
+        mime_types.sort();
+        Ok(mime_types)
+    }
 
-        Ok(read_into_string(&mut reader).map_err(Box::new)?)
+    /// Clears the selections `set_contents` copies to.
+    fn clear(&mut self) -> Result<()> {
+        clear(self.copy_target()?, copy::Seat::All).map_err(ClipboardError::from)
     }
 
-    /// Copies to the Wayland clipboard.
+    /// Copies to the Wayland clipboard and serves it for `duration`.
     ///
-    /// If the Wayland environment supported the primary selection when
-    /// this context was constructed, this will copy to both the
-    /// primary selection and the regular clipboard. Otherwise, only
-    /// the regular clipboard will be pasted to.
-    fn set_contents(&mut self, data: String) -> Result<()> {
+    /// Once `duration` has elapsed the clipboard is cleared, but only if
+    /// it still holds the copied data. Contents copied by another client
+    /// in the meantime are left alone.
+    fn set_contents_for_duration(&mut self, data: String, duration: Duration) -> Result<()> {
+        self.set_contents(data.clone())?;
+        thread::sleep(duration);
+        self.clear_if_unchanged(&data).map(drop)
+    }
+}
+
+impl WaylandClipboardContext {
+    fn copy_options(&self) -> Result<Options> {
         let mut options = Options::new();
 
         options
             .seat(copy::Seat::All)
             .trim_newline(false)
             .foreground(false)
-            .serve_requests(ServeRequests::Unlimited);
+            .serve_requests(ServeRequests::Unlimited)
+            .clipboard(self.copy_target()?);
 
-        if self.supports_primary_selection {
-            options.clipboard(copy::ClipboardType::Both);
-        } else {
-            options.clipboard(copy::ClipboardType::Regular);
+        Ok(options)
+    }
+
+    /// The selections copying and clearing operate on.
+    fn copy_target(&self) -> Result<copy::ClipboardType> {
+        Ok(match self.kind {
+            ClipboardKind::Clipboard => copy::ClipboardType::Regular,
+            ClipboardKind::Primary => copy::ClipboardType::Primary,
+            ClipboardKind::Secondary => return Err(secondary_selection_unsupported()),
+            ClipboardKind::Both if self.supports_primary_selection => copy::ClipboardType::Both,
+            ClipboardKind::Both => copy::ClipboardType::Regular,
+        })
+    }
+
+    /// Starts a paste of `mime_type` from the selection `kind`.
+    fn get_reader(&self, kind: ClipboardKind, mime_type: paste::MimeType) -> Result<impl Read> {
+        self.paste(kind, |clipboard| {
+            paste::get_contents(clipboard, paste::Seat::Unspecified, mime_type)
+                .map(|(reader, _)| reader)
+        })
+    }
+
+    /// Runs a paste operation on the selection `kind`.
+    ///
+    /// `ClipboardKind::Both` tries the regular clipboard first and falls
+    /// back to the primary selection if the clipboard is empty.
+    fn paste<T, F>(&self, kind: ClipboardKind, operation: F) -> Result<T>
+    where
+        F: Fn(paste::ClipboardType) -> std::result::Result<T, paste::Error>,
+    {
+        let result = match kind {
+            ClipboardKind::Clipboard => operation(paste::ClipboardType::Regular),
+            ClipboardKind::Primary => operation(paste::ClipboardType::Primary),
+            ClipboardKind::Secondary => return Err(secondary_selection_unsupported()),
+            ClipboardKind::Both => match operation(paste::ClipboardType::Regular) {
+                Err(paste::Error::ClipboardEmpty) if self.supports_primary_selection => {
+                    operation(paste::ClipboardType::Primary)
+                }
+                result => result,
+            },
+        };
+        result.map_err(ClipboardError::from)
+    }
+}
+
+/// Reports changes of the regular clipboard and the primary selection,
+/// using the `selection` and `primary_selection` events of the
+/// data-control protocol.
+///
+/// Iterating blocks until the next change on any seat. Changes of the
+/// primary selection are only reported if the compositor supports
+/// version 2 of the protocol.
+///
+/// # Example
+///
+/// ```noop
+/// use cli_clipboard::wayland_clipboard::WaylandWatcher;
+///
+/// for event in WaylandWatcher::new().unwrap() {
+///     let event = event.unwrap();
+///     println!("{:?} now offers {:?}", event.kind, event.mime_types);
+/// }
+/// ```
+pub struct WaylandWatcher {
+    queue: EventQueue,
+    events: Rc<RefCell<VecDeque<ClipboardEvent>>>,
+    // selection events are only sent to live devices
+    _devices: Vec<Main<ZwlrDataControlDeviceV1>>,
+    failed: bool,
+}
+
+impl WaylandWatcher {
+    /// Connects to the compositor and starts watching. Changes made
+    /// before this returns are not reported.
+    ///
+    /// Returns Err if the compositor does not support the data-control
+    /// protocol.
+    pub fn new() -> Result<WaylandWatcher> {
+        let (mut queue, manager, seats) = connect_data_control()?;
+
+        let events = Rc::new(RefCell::new(VecDeque::new()));
+        let devices = seats
+            .iter()
+            .map(|seat| {
+                let device = manager.get_data_device(seat);
+                let events = Rc::clone(&events);
+                device.quick_assign(move |_, event, _| {
+                    use zwlr_data_control_device_v1::Event::*;
+                    let (kind, offer) = match event {
+                        DataOffer { id } => return collect_mime_types(id),
+                        Selection { id } => (ClipboardKind::Clipboard, id),
+                        PrimarySelection { id } => (ClipboardKind::Primary, id),
+                        _ => return,
+                    };
+                    events.borrow_mut().push_back(ClipboardEvent {
+                        kind,
+                        mime_types: offer.map(take_mime_types).unwrap_or_default(),
+                    });
+                });
+                device
+            })
+            .collect();
+
+        // new devices are sent the current selections right away
+        queue.sync_roundtrip(&mut (), |_, _, _| {})?;
+        events.borrow_mut().clear();
+
+        Ok(WaylandWatcher {
+            queue,
+            events,
+            _devices: devices,
+            failed: false,
+        })
+    }
+}
+
+impl Iterator for WaylandWatcher {
+    type Item = Result<ClipboardEvent>;
+
+    /// Blocks until a selection changes. Ends after the connection to
+    /// the compositor fails.
+    fn next(&mut self) -> Option<Result<ClipboardEvent>> {
+        if self.failed {
+            return None;
         }
+        loop {
+            if let Some(event) = self.events.borrow_mut().pop_front() {
+                return Some(Ok(event));
+            }
+            if let Err(e) = self.queue.dispatch(&mut (), |_, _, _| {}) {
+                self.failed = true;
+                return Some(Err(e.into()));
+            }
+        }
+    }
+}
 
-        options
-            .copy(
-                copy::Source::Bytes(data.into_bytes().into()),
-                copy::MimeType::Text,
-            )
-            .map_err(into_boxed_error)
+/// Connects to the compositor and binds the data-control manager, at
+/// version 2 if the primary selection is supported, and all seats.
+fn connect_data_control() -> Result<(
+    EventQueue,
+    Main<ZwlrDataControlManagerV1>,
+    Vec<Main<WlSeat>>,
+)> {
+    let display =
+        Display::connect_to_env().map_err(|e| ClipboardError::NoDisplayServer(Box::new(e)))?;
+    let mut queue = display.create_event_queue();
+    let display = display.attach(queue.token());
+
+    let seats = Rc::new(RefCell::new(Vec::<Main<WlSeat>>::new()));
+    let new_seats = Rc::clone(&seats);
+    let globals = GlobalManager::new_with_cb(
+        &display,
+        global_filter!([WlSeat, 2, move |seat: Main<WlSeat>, _: DispatchData| {
+            new_seats.borrow_mut().push(seat)
+        }]),
+    );
+    queue.sync_roundtrip(&mut (), |_, _, _| {})?;
+
+    let manager = globals
+        .instantiate_range::<ZwlrDataControlManagerV1>(1, 2)
+        .map_err(|_| missing_protocol(ZwlrDataControlManagerV1::NAME, 1))?;
+    let seats = seats.borrow().clone();
+    Ok((queue, manager, seats))
+}
+
+/// Sensitive contents being served by the crate's own data sources.
+struct SensitiveOffer {
+    queue: EventQueue,
+    data: Rc<RefCell<Zeroizing<Vec<u8>>>>,
+    sources: Rc<Cell<usize>>,
+    _devices: Vec<Main<ZwlrDataControlDeviceV1>>,
+}
+
+impl SensitiveOffer {
+    /// Serves requests until every source has been replaced or the
+    /// connection fails, then overwrites the data with zeros.
+    fn run(mut self) {
+        while self.sources.get() > 0 {
+            if self.queue.dispatch(&mut (), |_, _, _| {}).is_err() {
+                break;
+            }
+        }
+        self.data.borrow_mut().zeroize();
     }
+}
 
-    fn clear(&mut self) -> Result<()> {
-        if self.supports_primary_selection {
-            clear(copy::ClipboardType::Both, copy::Seat::All).map_err(into_boxed_error)
-        } else {
-            clear(copy::ClipboardType::Regular, copy::Seat::All).map_err(into_boxed_error)
+/// Sets the `target` selections of every seat to `data` as plain text,
+/// along with the password manager hint.
+fn offer_sensitive(
+    target: copy::ClipboardType,
+    data: Zeroizing<Vec<u8>>,
+) -> Result<SensitiveOffer> {
+    let (mut queue, manager, seats) = connect_data_control()?;
+    if seats.is_empty() {
+        return Err(ClipboardError::Backend("no seats to copy to".into()));
+    }
+    let supports_primary = manager.as_ref().version() >= 2;
+    let (regular, primary) = match target {
+        copy::ClipboardType::Regular => (true, false),
+        copy::ClipboardType::Primary if !supports_primary => {
+            return Err(primary_selection_unsupported())
         }
+        copy::ClipboardType::Primary => (false, true),
+        copy::ClipboardType::Both => (true, supports_primary),
+    };
+
+    let data = Rc::new(RefCell::new(data));
+    let sources = Rc::new(Cell::new(0));
+    let devices = seats
+        .iter()
+        .map(|seat| {
+            let device = manager.get_data_device(seat);
+            // the offers of other clients are of no interest here
+            device.quick_assign(|_, event, _| {
+                use zwlr_data_control_device_v1::Event::*;
+                if let Selection { id: Some(offer) } | PrimarySelection { id: Some(offer) } = event
+                {
+                    offer.destroy();
+                }
+            });
+            if regular {
+                device.set_selection(Some(&sensitive_source(&manager, &data, &sources)));
+            }
+            if primary {
+                device.set_primary_selection(Some(&sensitive_source(&manager, &data, &sources)));
+            }
+            device
+        })
+        .collect();
+    queue.sync_roundtrip(&mut (), |_, _, _| {})?;
+
+    Ok(SensitiveOffer {
+        queue,
+        data,
+        sources,
+        _devices: devices,
+    })
+}
+
+/// Creates a source that offers `data` as plain text along with the
+/// password manager hint, counted in `sources` until it is cancelled.
+fn sensitive_source(
+    manager: &ZwlrDataControlManagerV1,
+    data: &Rc<RefCell<Zeroizing<Vec<u8>>>>,
+    sources: &Rc<Cell<usize>>,
+) -> Main<ZwlrDataControlSourceV1> {
+    let source = manager.create_data_source();
+    for mime_type in TEXT_MIME_TYPES.iter().chain(&[PASSWORD_MANAGER_HINT]) {
+        source.offer((*mime_type).to_owned());
     }
+    sources.set(sources.get() + 1);
+
+    let data = Rc::clone(data);
+    let sources = Rc::clone(sources);
+    source.quick_assign(move |source, event, _| match event {
+        zwlr_data_control_source_v1::Event::Send { mime_type, fd } => {
+            let mut pipe = unsafe { File::from_raw_fd(fd) };
+            // the requestor may have stopped reading already
+            let _ = if mime_type == PASSWORD_MANAGER_HINT {
+                pipe.write_all(PASSWORD_MANAGER_HINT_VALUE)
+            } else {
+                pipe.write_all(&data.borrow())
+            };
+        }
+        zwlr_data_control_source_v1::Event::Cancelled => {
+            source.destroy();
+            sources.set(sources.get() - 1);
+        }
+        _ => (),
+    });
+    source
+}
+
+/// Records the MIME types announced for a new data offer.
+fn collect_mime_types(offer: Main<ZwlrDataControlOfferV1>) {
+    offer
+        .as_ref()
+        .user_data()
+        .set(|| RefCell::new(Vec::<String>::new()));
+    offer.quick_assign(|offer, event, _| {
+        if let zwlr_data_control_offer_v1::Event::Offer { mime_type } = event {
+            if let Some(mime_types) = offer.as_ref().user_data().get::<RefCell<Vec<String>>>() {
+                mime_types.borrow_mut().push(mime_type);
+            }
+        }
+    });
+}
+
+/// Returns the MIME types recorded for `offer`, sorted by name, and
+/// destroys it.
+fn take_mime_types(offer: ZwlrDataControlOfferV1) -> Vec<String> {
+    let mut mime_types = offer
+        .as_ref()
+        .user_data()
+        .get::<RefCell<Vec<String>>>()
+        .map(|mime_types| mime_types.replace(Vec::new()))
+        .unwrap_or_default();
+    offer.destroy();
+    mime_types.sort();
+    mime_types.dedup();
+    mime_types
+}
+
+impl From<utils::PrimarySelectionCheckError> for ClipboardError {
+    fn from(err: utils::PrimarySelectionCheckError) -> ClipboardError {
+        use utils::PrimarySelectionCheckError::*;
+        match err {
+            WaylandConnection(e) => ClipboardError::NoDisplayServer(Box::new(e)),
+            WaylandCommunication(e) => ClipboardError::Io(e),
+            MissingProtocol { name, version } => missing_protocol(name, version),
+            e => ClipboardError::Backend(e.to_string().into()),
+        }
+    }
+}
+
+impl From<paste::Error> for ClipboardError {
+    fn from(err: paste::Error) -> ClipboardError {
+        use paste::Error::*;
+        match err {
+            WaylandConnection(e) => ClipboardError::NoDisplayServer(Box::new(e)),
+            WaylandCommunication(e) | PipeCreation(e) => ClipboardError::Io(e),
+            MissingProtocol { name, version } => missing_protocol(name, version),
+            PrimarySelectionUnsupported => primary_selection_unsupported(),
+            NoSeats | ClipboardEmpty => ClipboardError::Empty,
+            NoMimeType => ClipboardError::FormatUnavailable("a suitable MIME type".to_owned()),
+            e => ClipboardError::Backend(e.to_string().into()),
+        }
+    }
+}
+
+impl From<copy::Error> for ClipboardError {
+    fn from(err: copy::Error) -> ClipboardError {
+        use copy::Error::*;
+        match err {
+            WaylandConnection(e) => ClipboardError::NoDisplayServer(Box::new(e)),
+            WaylandCommunication(e) | TempFileRemove(e) | TempDirRemove(e) => ClipboardError::Io(e),
+            MissingProtocol { name, version } => missing_protocol(name, version),
+            PrimarySelectionUnsupported => primary_selection_unsupported(),
+            e => ClipboardError::Backend(e.to_string().into()),
+        }
+    }
+}
+
+fn missing_protocol(name: &str, version: u32) -> ClipboardError {
+    ClipboardError::UnsupportedProtocol(format!("{} version {}", name, version))
+}
+
+fn primary_selection_unsupported() -> ClipboardError {
+    ClipboardError::UnsupportedProtocol("the primary selection".to_owned())
 }
 
-fn into_boxed_error<F: 'static + Fail>(fail: F) -> anyhow::Error {
-    fail.compat().into()
+fn secondary_selection_unsupported() -> ClipboardError {
+    ClipboardError::Unsupported("the secondary selection on Wayland".to_owned())
 }
 
-fn read_into_string<R: Read>(reader: &mut R) -> io::Result<String> {
-    let mut contents = String::new();
-    reader.read_to_string(&mut contents)?;
+fn read_into_string<R: Read>(reader: &mut R) -> Result<String> {
+    let mut contents = Vec::new();
+    reader.read_to_end(&mut contents)?;
 
-    Ok(contents)
+    Ok(String::from_utf8(contents)?)
 }
 
 #[cfg(test)]
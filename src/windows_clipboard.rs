@@ -14,10 +14,12 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
-use clipboard_win::{empty, get_clipboard_string, set_clipboard_string, Clipboard};
+use clipboard_win::{
+    empty, formats, get_clipboard_string, is_format_avail, set_clipboard_string, Clipboard,
+};
 
 use crate::common::ClipboardProvider;
-use anyhow::Result;
+use crate::error::{ClipboardError, Result};
 
 pub struct WindowsClipboardContext;
 
@@ -27,15 +29,25 @@ impl ClipboardProvider for WindowsClipboardContext {
     }
 
     fn get_contents(&mut self) -> Result<String> {
-        Ok(get_clipboard_string()?)
+        if !is_format_avail(formats::CF_UNICODETEXT) {
+            return Err(ClipboardError::Empty);
+        }
+        get_clipboard_string().map_err(backend_error)
     }
 
     fn set_contents(&mut self, data: String) -> Result<()> {
-        Ok(set_clipboard_string(&data)?)
+        set_clipboard_string(&data).map_err(backend_error)
     }
 
     fn clear(&mut self) -> Result<()> {
-        let _clip = Clipboard::new_attempts(10)?;
-        Ok(empty()?)
+        let _clip = Clipboard::new_attempts(10).map_err(backend_error)?;
+        empty().map_err(backend_error)
     }
 }
+
+fn backend_error<E>(err: E) -> ClipboardError
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    ClipboardError::Backend(Box::new(err))
+}
@@ -15,12 +15,20 @@ limitations under the License.
 */
 
 use crate::common::*;
-use anyhow::Result;
+use crate::error::{ClipboardError, Result};
+use crate::x11_server::{Offer, SelectionServer};
+use std::io::{self, Write};
 use std::marker::PhantomData;
-use std::time::Duration;
-use x11_clipboard_crate::xcb::xproto::Atom;
+use std::os::unix::io::AsRawFd;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use x11_clipboard_crate::error::Error as X11Error;
+use x11_clipboard_crate::xcb;
+use x11_clipboard_crate::xcb::xproto::{Atom, Window};
 use x11_clipboard_crate::Atoms;
-use x11_clipboard_crate::Clipboard as X11Clipboard;
+use x11_clipboard_crate::Context;
+use zeroize::Zeroizing;
 
 pub trait Selection {
     fn atom(atoms: &Atoms) -> Atom;
@@ -42,40 +50,661 @@ impl Selection for Clipboard {
     }
 }
 
-pub struct X11ClipboardContext<S = Clipboard>(X11Clipboard, PhantomData<S>)
+pub struct Secondary;
+
+impl Selection for Secondary {
+    fn atom(_: &Atoms) -> Atom {
+        xcb::ATOM_SECONDARY
+    }
+}
+
+/// The selection atoms for `kind` in the order they are read from, the
+/// runtime equivalent of `Selection::atom`.
+fn kind_atoms(kind: ClipboardKind, atoms: &Atoms) -> Vec<Atom> {
+    match kind {
+        ClipboardKind::Clipboard => vec![Clipboard::atom(atoms)],
+        ClipboardKind::Primary => vec![Primary::atom(atoms)],
+        ClipboardKind::Secondary => vec![Secondary::atom(atoms)],
+        ClipboardKind::Both => vec![Clipboard::atom(atoms), Primary::atom(atoms)],
+    }
+}
+
+impl From<X11Error> for ClipboardError {
+    fn from(err: X11Error) -> ClipboardError {
+        match err {
+            X11Error::XcbConn(e) => ClipboardError::NoDisplayServer(Box::new(e)),
+            X11Error::Timeout => ClipboardError::Timeout,
+            e => ClipboardError::Backend(e.to_string().into()),
+        }
+    }
+}
+
+// How often set_contents_for_duration checks whether another client
+// has taken over the selection.
+const OWNER_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long to wait for the selection owner unless configured otherwise,
+/// see `X11ClipboardContext::set_timeout`.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(3);
+
+// Targets that are part of the selection protocol rather than formats
+// of the contents, and have no counterpart on Wayland.
+const PROTOCOL_TARGETS: [&str; 7] = [
+    "TARGETS",
+    "MULTIPLE",
+    "TIMESTAMP",
+    "SAVE_TARGETS",
+    "DELETE",
+    "INSERT_SELECTION",
+    "INSERT_PROPERTY",
+];
+
+pub struct X11ClipboardContext<S = Clipboard>
+where
+    S: Selection,
+{
+    getter: Context,
+    server: SelectionServer,
+    timeout: Duration,
+    selections: Vec<Atom>,
+    marker: PhantomData<S>,
+}
+
+impl<S> X11ClipboardContext<S>
 where
-    S: Selection;
+    S: Selection,
+{
+    /// Switches the selection this context operates on at runtime,
+    /// overriding the one given by `S`.
+    ///
+    /// With `ClipboardKind::Both`, copies are offered on both CLIPBOARD
+    /// and PRIMARY, and pastes read CLIPBOARD unless it has no owner.
+    pub fn set_kind(&mut self, kind: ClipboardKind) {
+        self.selections = kind_atoms(kind, &self.getter.atoms);
+    }
+
+    /// Like `set_kind`, for use right after construction.
+    pub fn with_kind(mut self, kind: ClipboardKind) -> X11ClipboardContext<S> {
+        self.set_kind(kind);
+        self
+    }
+
+    /// Sets how long reads wait for the selection owner to answer, or to
+    /// send the next chunk of a large selection, before failing with
+    /// `ClipboardError::Timeout`. Defaults to `DEFAULT_TIMEOUT`.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Like `set_timeout`, for use right after construction.
+    pub fn with_timeout(mut self, timeout: Duration) -> X11ClipboardContext<S> {
+        self.timeout = timeout;
+        self
+    }
+
+    /// How long reads wait for the selection owner.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Resolves the target atoms X11 clients use for `mime_type`.
+    ///
+    /// Plain text maps to `UTF8_STRING` and its MIME type, every other
+    /// MIME type is used as the atom name, which is the convention
+    /// followed by toolkits.
+    fn target_atoms(context: &Context, mime_type: &str) -> Result<Vec<Atom>> {
+        if is_plain_text(mime_type) {
+            Ok(vec![
+                context.atoms.utf8_string,
+                context.get_atom("text/plain;charset=utf-8")?,
+            ])
+        } else {
+            Ok(vec![context.get_atom(mime_type)?])
+        }
+    }
+
+    /// Offers every representation in `contents` under all of its
+    /// target atoms. The first representation for a target wins.
+    fn store(&self, contents: Vec<ClipboardContent>) -> Result<()> {
+        let context = self.server.context();
+        let mut offer = Offer::new();
+        for content in contents {
+            let targets = Self::target_atoms(context, content.mime_type())?;
+            let data = Arc::new(Zeroizing::new(content.into_bytes()));
+            for target in targets {
+                if offer.iter().all(|&(offered, _)| offered != target) {
+                    offer.push((target, Arc::clone(&data)));
+                }
+            }
+        }
+
+        for &selection in &self.selections {
+            self.server.offer(selection, offer.clone())?;
+        }
+        Ok(())
+    }
+
+    fn atom_name(&self, atom: Atom) -> Result<String> {
+        let reply = xcb::get_atom_name(&self.getter.connection, atom)
+            .get_reply()
+            .map_err(X11Error::from)?;
+        Ok(reply.name().to_owned())
+    }
+
+    /// Converts the selection to `target` and reads the result.
+    fn load(&self, target: Atom) -> Result<Vec<u8>> {
+        self.load_as(target, target)
+    }
+
+    /// Converts the selection to `target` and reads the result, which
+    /// the selection owner must deliver as `expected_type`.
+    fn load_as(&self, target: Atom, expected_type: Atom) -> Result<Vec<u8>> {
+        let mut buff = Vec::new();
+        self.load_into(target, expected_type, &mut buff)?;
+        Ok(buff)
+    }
+
+    /// Converts the first selection that has an owner to `target` and
+    /// writes the result to `writer`, returning the number of bytes
+    /// written.
+    fn load_into(&self, target: Atom, expected_type: Atom, writer: &mut dyn Write) -> Result<u64> {
+        let (last, rest) = self
+            .selections
+            .split_last()
+            .expect("a context has at least one selection");
+        for &selection in rest {
+            match self.load_selection(selection, target, expected_type, writer) {
+                Err(ClipboardError::Empty) => (),
+                result => return result,
+            }
+        }
+        self.load_selection(*last, target, expected_type, writer)
+    }
+
+    /// Converts `selection` to `target` and writes the result to
+    /// `writer` as it arrives, returning the number of bytes written.
+    ///
+    /// Large selections arrive in chunks through the INCR protocol. The
+    /// timeout applies to each chunk rather than to the whole transfer,
+    /// so that multi-megabyte transfers complete as long as the owner
+    /// keeps sending.
+    ///
+    /// Returns `ClipboardError::Empty` if the selection has no owner and
+    /// `ClipboardError::FormatUnavailable` if the owner refuses to
+    /// convert the selection to `target`.
+    fn load_selection(
+        &self,
+        selection: Atom,
+        target: Atom,
+        expected_type: Atom,
+        writer: &mut dyn Write,
+    ) -> Result<u64> {
+        let context = &self.getter;
+        let property = context.atoms.property;
+
+        let owner = xcb::get_selection_owner(&context.connection, selection)
+            .get_reply()
+            .map_err(X11Error::from)?
+            .owner();
+        if owner == xcb::NONE {
+            return Err(ClipboardError::Empty);
+        }
+
+        xcb::convert_selection(
+            &context.connection,
+            context.window,
+            selection,
+            target,
+            property,
+            xcb::CURRENT_TIME,
+        );
+        context.connection.flush();
+
+        let mut written = 0;
+        let mut is_incr = false;
+        let mut deadline = Instant::now() + self.timeout;
+        loop {
+            if Instant::now() >= deadline {
+                return Err(X11Error::Timeout.into());
+            }
+
+            let event = match context.connection.poll_for_event() {
+                Some(event) => event,
+                None => {
+                    wait_for_events(&context.connection, deadline)?;
+                    continue;
+                }
+            };
+
+            match event.response_type() & !0x80 {
+                xcb::SELECTION_NOTIFY => {
+                    let event = unsafe { xcb::cast_event::<xcb::SelectionNotifyEvent>(&event) };
+                    if event.selection() != selection {
+                        continue;
+                    }
+
+                    // the owner could not convert the selection
+                    if event.property() == xcb::ATOM_NONE {
+                        return Err(ClipboardError::FormatUnavailable(self.atom_name(target)?));
+                    }
+
+                    let reply = xcb::get_property(
+                        &context.connection,
+                        false,
+                        context.window,
+                        property,
+                        xcb::ATOM_ANY,
+                        0,
+                        u32::MAX,
+                    )
+                    .get_reply()
+                    .map_err(X11Error::from)?;
+
+                    if reply.type_() == context.atoms.incr {
+                        xcb::delete_property(&context.connection, context.window, property);
+                        context.connection.flush();
+                        is_incr = true;
+                        deadline = Instant::now() + self.timeout;
+                        continue;
+                    } else if reply.type_() != expected_type {
+                        return Err(X11Error::UnexpectedType(reply.type_()).into());
+                    }
+
+                    // value_len counts items of the property's format, not bytes
+                    let value = reply.value::<u8>();
+                    writer.write_all(value)?;
+                    written += value.len() as u64;
+                    break;
+                }
+                xcb::PROPERTY_NOTIFY if is_incr => {
+                    let event = unsafe { xcb::cast_event::<xcb::PropertyNotifyEvent>(&event) };
+                    if event.atom() != property || event.state() != xcb::PROPERTY_NEW_VALUE as u8 {
+                        continue;
+                    }
+
+                    let reply = xcb::get_property(
+                        &context.connection,
+                        true,
+                        context.window,
+                        property,
+                        xcb::ATOM_ANY,
+                        0,
+                        u32::MAX,
+                    )
+                    .get_reply()
+                    .map_err(X11Error::from)?;
+                    context.connection.flush();
+
+                    if reply.type_() != expected_type {
+                        continue;
+                    }
+                    if reply.value_len() == 0 {
+                        break;
+                    }
+                    // value_len counts items of the property's format, not bytes
+                    let value = reply.value::<u8>();
+                    writer.write_all(value)?;
+                    written += value.len() as u64;
+                    deadline = Instant::now() + self.timeout;
+                }
+                _ => (),
+            }
+        }
+
+        xcb::delete_property(&context.connection, context.window, property);
+        context.connection.flush();
+        Ok(written)
+    }
+}
+
+/// Blocks until the X server sends something or `deadline` passes.
+fn wait_for_events(connection: &xcb::Connection, deadline: Instant) -> Result<()> {
+    let timeout = deadline.saturating_duration_since(Instant::now());
+    let mut fd = libc::pollfd {
+        fd: connection.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let timeout = timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+    if unsafe { libc::poll(&mut fd, 1, timeout) } == -1 {
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err.into());
+        }
+    }
+    Ok(())
+}
 
 impl<S> ClipboardProvider for X11ClipboardContext<S>
 where
     S: Selection,
 {
+    /// Connects to the X server, operating on the selection of `S`.
     fn new() -> Result<X11ClipboardContext<S>> {
-        Ok(X11ClipboardContext(X11Clipboard::new()?, PhantomData))
+        let getter = Context::new(None)?;
+        let selections = vec![S::atom(&getter.atoms)];
+        Ok(X11ClipboardContext {
+            getter,
+            server: SelectionServer::new()?,
+            timeout: DEFAULT_TIMEOUT,
+            selections,
+            marker: PhantomData,
+        })
     }
 
     fn get_contents(&mut self) -> Result<String> {
-        Ok(String::from_utf8(self.0.load(
-            S::atom(&self.0.getter.atoms),
-            self.0.getter.atoms.utf8_string,
-            self.0.getter.atoms.property,
-            Duration::from_secs(3),
-        )?)?)
+        Ok(String::from_utf8(
+            self.load(self.getter.atoms.utf8_string)?,
+        )?)
     }
 
     fn set_contents(&mut self, data: String) -> Result<()> {
-        Ok(self.0.store(
-            S::atom(&self.0.setter.atoms),
-            self.0.setter.atoms.utf8_string,
-            data,
-        )?)
+        self.store(vec![ClipboardContent::Text(data)])
     }
 
+    /// Clears the X11 selection, leaving it without an owner.
     fn clear(&mut self) -> Result<()> {
-        Ok(self.0.store(
-            S::atom(&self.0.setter.atoms),
-            self.0.setter.atoms.utf8_string,
-            "".to_string(),
-        )?)
+        for &selection in &self.selections {
+            self.server.clear(selection);
+        }
+        Ok(())
+    }
+
+    fn get_content(&mut self, mime_type: &str) -> Result<ClipboardContent> {
+        ClipboardContent::from_bytes(mime_type, self.get_bytes(mime_type)?)
+    }
+
+    fn get_bytes(&mut self, mime_type: &str) -> Result<Vec<u8>> {
+        let target = Self::target_atoms(&self.getter, mime_type)?[0];
+        self.load(target)
+    }
+
+    fn set_bytes(&mut self, mime_type: &str, data: Vec<u8>) -> Result<()> {
+        self.store(vec![ClipboardContent::Other {
+            mime_type: mime_type.to_owned(),
+            data,
+        }])
+    }
+
+    fn set_content(&mut self, content: ClipboardContent) -> Result<()> {
+        self.store(vec![content])
+    }
+
+    /// Writes the `mime_type` contents to `writer` as they arrive, so
+    /// that large INCR transfers are never held in memory as a whole.
+    fn paste_to_writer(&mut self, mime_type: &str, writer: &mut dyn Write) -> Result<u64> {
+        let target = Self::target_atoms(&self.getter, mime_type)?[0];
+        self.load_into(target, target, writer)
+    }
+
+    /// Offers all `contents` at once.
+    ///
+    /// Requests for `TARGETS` are answered with the target atoms of
+    /// every representation, and each target is served with the data
+    /// of its representation.
+    fn set_content_multi(&mut self, contents: Vec<ClipboardContent>) -> Result<()> {
+        if contents.is_empty() {
+            return Err(no_representations());
+        }
+        self.store(contents)
+    }
+
+    /// Offers `data` along with the `x-kde-passwordManagerHint` target.
+    ///
+    /// The data is overwritten with zeros once it is no longer offered,
+    /// i.e. when another client takes over the selection or it is
+    /// cleared or released, and any INCR transfer of it has finished.
+    fn set_sensitive_contents(&mut self, data: String) -> Result<()> {
+        self.store(vec![ClipboardContent::Text(data), password_manager_hint()])
+    }
+
+    /// Lists the targets offered by the selection owner.
+    ///
+    /// These are the names of the atoms the owner answers `TARGETS`
+    /// with. Besides MIME types they usually include X11-specific
+    /// targets such as `UTF8_STRING`, `TARGETS` or `TIMESTAMP`.
+    fn available_formats(&mut self) -> Result<Vec<String>> {
+        let data = match self.load_as(self.getter.atoms.targets, xcb::ATOM_ATOM) {
+            Ok(data) => data,
+            Err(ClipboardError::Empty) => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        data.chunks_exact(4)
+            .map(|atom| self.atom_name(Atom::from_ne_bytes([atom[0], atom[1], atom[2], atom[3]])))
+            .collect()
+    }
+
+    /// Copies to the X11 selection and serves it until `duration` has
+    /// elapsed or another client takes ownership of the selection,
+    /// whichever comes first. With `ClipboardKind::Both` this waits until
+    /// other clients own both selections.
+    ///
+    /// If this context still owns the selection once `duration` has
+    /// elapsed, ownership is given up so that other clients stop
+    /// expecting this process to answer requests for it.
+    fn set_contents_for_duration(&mut self, data: String, duration: Duration) -> Result<()> {
+        self.set_contents(data)?;
+
+        let deadline = Instant::now() + duration;
+        loop {
+            if !self.selections.iter().any(|&s| self.server.owns(s)) {
+                return Ok(());
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            thread::sleep(OWNER_POLL_INTERVAL.min(deadline - now));
+        }
+
+        for &selection in &self.selections {
+            self.server.release(selection);
+        }
+        Ok(())
+    }
+
+    /// Gives up the selections this context still owns, ignoring `data`.
+    ///
+    /// A selection is only owned until another client copies, so owning
+    /// it means it still holds what this context copied.
+    fn clear_if_unchanged(&mut self, _data: &str) -> Result<bool> {
+        let mut cleared = false;
+        for &selection in &self.selections {
+            cleared |= self.server.owns(selection);
+            self.server.release(selection);
+        }
+        Ok(cleared)
+    }
+}
+
+/// Reports changes of the CLIPBOARD and PRIMARY selections, using the
+/// XFixes extension.
+///
+/// Iterating blocks until the next change. Each event lists the targets
+/// the new owner offers as they are, e.g. `UTF8_STRING` next to MIME
+/// types, like `WaylandWatcher` does. Only targets of the selection
+/// protocol itself, such as `TARGETS` and `TIMESTAMP`, are left out.
+///
+/// # Example
+///
+/// ```noop
+/// use cli_clipboard::x11_clipboard::X11Watcher;
+///
+/// for event in X11Watcher::new().unwrap() {
+///     let event = event.unwrap();
+///     println!("{:?} now offers {:?}", event.kind, event.mime_types);
+/// }
+/// ```
+pub struct X11Watcher {
+    context: Context,
+    first_event: u8,
+    reader: X11ClipboardContext,
+    failed: bool,
+}
+
+impl X11Watcher {
+    /// Connects to the X server and starts watching. Changes made before
+    /// this returns are not reported.
+    ///
+    /// Returns `ClipboardError::UnsupportedProtocol` if the X server
+    /// lacks the XFixes extension.
+    pub fn new() -> Result<X11Watcher> {
+        let context = Context::new(None)?;
+        let first_event = context
+            .connection
+            .get_extension_data(xcb::xfixes::id())
+            .filter(|data| data.present())
+            .map(|data| data.first_event())
+            .ok_or_else(|| ClipboardError::UnsupportedProtocol("XFixes".to_owned()))?;
+        xcb::xfixes::query_version(
+            &context.connection,
+            xcb::xfixes::MAJOR_VERSION,
+            xcb::xfixes::MINOR_VERSION,
+        )
+        .get_reply()
+        .map_err(X11Error::from)?;
+
+        let mask = xcb::xfixes::SELECTION_EVENT_MASK_SET_SELECTION_OWNER
+            | xcb::xfixes::SELECTION_EVENT_MASK_SELECTION_WINDOW_DESTROY
+            | xcb::xfixes::SELECTION_EVENT_MASK_SELECTION_CLIENT_CLOSE;
+        for &selection in &[context.atoms.clipboard, context.atoms.primary] {
+            xcb::xfixes::select_selection_input(
+                &context.connection,
+                context.window,
+                selection,
+                mask,
+            );
+        }
+        context.connection.flush();
+
+        Ok(X11Watcher {
+            context,
+            first_event,
+            reader: X11ClipboardContext::new()?,
+            failed: false,
+        })
+    }
+
+    /// Builds the event for `selection` having changed to `owner`.
+    ///
+    /// If the owner doesn't answer the request for its targets, e.g.
+    /// because it already exited, the event has no MIME types. Only
+    /// losing the connection is an error.
+    fn event(&mut self, selection: Atom, owner: Window) -> Result<ClipboardEvent> {
+        let kind = if selection == self.context.atoms.primary {
+            ClipboardKind::Primary
+        } else {
+            ClipboardKind::Clipboard
+        };
+        if owner == xcb::NONE {
+            return Ok(ClipboardEvent {
+                kind,
+                mime_types: Vec::new(),
+            });
+        }
+
+        self.reader.set_kind(kind);
+        let targets = match self.reader.available_formats() {
+            Ok(targets) => targets,
+            Err(e @ ClipboardError::NoDisplayServer(_)) => {
+                self.failed = true;
+                return Err(e);
+            }
+            Err(_) => Vec::new(),
+        };
+        let mut mime_types: Vec<String> = targets
+            .into_iter()
+            .filter(|target| !PROTOCOL_TARGETS.contains(&target.as_str()))
+            .collect();
+        mime_types.sort();
+        mime_types.dedup();
+        Ok(ClipboardEvent { kind, mime_types })
+    }
+}
+
+impl Iterator for X11Watcher {
+    type Item = Result<ClipboardEvent>;
+
+    /// Blocks until a selection changes. Ends after the connection to
+    /// the X server fails.
+    fn next(&mut self) -> Option<Result<ClipboardEvent>> {
+        if self.failed {
+            return None;
+        }
+        loop {
+            let event = match self.context.connection.wait_for_event() {
+                Some(event) => event,
+                None => {
+                    self.failed = true;
+                    let err = match self.context.connection.has_error() {
+                        Err(e) => X11Error::XcbConn(e).into(),
+                        Ok(()) => ClipboardError::Backend("lost the X11 connection".into()),
+                    };
+                    return Some(Err(err));
+                }
+            };
+            if event.response_type() & !0x80 == self.first_event + xcb::xfixes::SELECTION_NOTIFY {
+                let event = unsafe { xcb::cast_event::<xcb::xfixes::SelectionNotifyEvent>(&event) };
+                return Some(self.event(event.selection(), event.owner()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[ignore]
+    fn incr_round_trip() {
+        // larger than the maximum request size, so that it is served and
+        // received through INCR
+        let data: String = (0..8 * 1024 * 1024)
+            .map(|i| char::from(b'a' + (i % 26) as u8))
+            .collect();
+
+        let mut owner: X11ClipboardContext =
+            X11ClipboardContext::new().expect("couldn't create an X11 clipboard");
+        owner
+            .set_contents(data.clone())
+            .expect("couldn't set contents of X11 clipboard");
+
+        let mut reader: X11ClipboardContext =
+            X11ClipboardContext::new().expect("couldn't create an X11 clipboard");
+        let pasted = reader
+            .get_contents()
+            .expect("couldn't get contents of X11 clipboard");
+        assert_eq!(pasted.len(), data.len());
+        assert!(pasted == data);
+    }
+
+    #[test]
+    #[ignore]
+    fn watch_reports_changes() {
+        let mut watcher = X11Watcher::new().expect("couldn't watch the X11 selections");
+        let mut owner: X11ClipboardContext<Primary> =
+            X11ClipboardContext::new().expect("couldn't create an X11 clipboard");
+        owner
+            .set_contents("foo bar baz".to_owned())
+            .expect("couldn't set contents of X11 clipboard");
+
+        let event = watcher.next().unwrap().expect("couldn't watch");
+        assert_eq!(event.kind, ClipboardKind::Primary);
+        assert!(event
+            .mime_types
+            .contains(&"text/plain;charset=utf-8".to_owned()));
+        assert!(event.mime_types.contains(&"UTF8_STRING".to_owned()));
+        assert!(!event.mime_types.contains(&"TARGETS".to_owned()));
+
+        owner
+            .set_sensitive_contents("hunter2".to_owned())
+            .expect("couldn't set contents of X11 clipboard");
+        let event = watcher.next().unwrap().expect("couldn't watch");
+        assert!(event.mime_types.contains(&PASSWORD_MANAGER_HINT.to_owned()));
+
+        owner.clear().unwrap();
+        let event = watcher.next().unwrap().expect("couldn't watch");
+        assert_eq!(event.mime_types, Vec::<String>::new());
     }
 }
@@ -0,0 +1,377 @@
+//! Serving X11 selections in any number of targets.
+//!
+//! `x11_clipboard::Clipboard` can only offer a single target per
+//! selection, so the X11 provider answers selection requests itself.
+
+use crate::error::Result;
+use std::cmp;
+use std::collections::HashMap;
+use std::os::unix::io::AsRawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use x11_clipboard_crate::error::Error as X11Error;
+use x11_clipboard_crate::xcb;
+use x11_clipboard_crate::xcb::xproto::{Atom, Window};
+use x11_clipboard_crate::Context;
+use zeroize::Zeroizing;
+
+/// Data offered for a target, overwritten with zeros once no longer
+/// offered or transferred, since it may be a secret.
+pub type OfferData = Arc<Zeroizing<Vec<u8>>>;
+
+/// Data offered for a selection as (target, data) pairs, in order of
+/// preference.
+pub type Offer = Vec<(Atom, OfferData)>;
+
+type OfferMap = Arc<Mutex<HashMap<Atom, Offer>>>;
+
+// How long a requestor may take to delete the property before its INCR
+// transfer is abandoned.
+const TRANSFER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// An INCR transfer in progress, keyed by requestor window and property.
+struct Transfer {
+    target: Atom,
+    data: OfferData,
+    pos: usize,
+    deadline: Instant,
+}
+
+/// Owns X11 selections and answers requests for them on a background
+/// thread until dropped.
+pub struct SelectionServer {
+    context: Arc<Context>,
+    offers: OfferMap,
+    shutdown: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SelectionServer {
+    pub fn new() -> Result<SelectionServer> {
+        let context = Arc::new(Context::new(None)?);
+        let offers = OfferMap::default();
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let context = Arc::clone(&context);
+            let offers = Arc::clone(&offers);
+            let shutdown = Arc::clone(&shutdown);
+            thread::spawn(move || run(&context, &offers, &shutdown))
+        };
+
+        Ok(SelectionServer {
+            context,
+            offers,
+            shutdown,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    /// Takes ownership of `selection` and serves `offer` for it until
+    /// another client takes the selection over.
+    pub fn offer(&self, selection: Atom, offer: Offer) -> Result<()> {
+        self.offers
+            .lock()
+            .map_err(|_| X11Error::Lock)?
+            .insert(selection, offer);
+
+        xcb::set_selection_owner(
+            &self.context.connection,
+            self.context.window,
+            selection,
+            xcb::CURRENT_TIME,
+        );
+        self.context.connection.flush();
+
+        if self.owns(selection) {
+            Ok(())
+        } else {
+            self.forget(selection);
+            Err(X11Error::Owner.into())
+        }
+    }
+
+    /// Returns whether this server currently owns `selection`.
+    pub fn owns(&self, selection: Atom) -> bool {
+        xcb::get_selection_owner(&self.context.connection, selection)
+            .get_reply()
+            .map(|reply| reply.owner() == self.context.window)
+            .unwrap_or(false)
+    }
+
+    /// Gives up ownership of `selection` if this server holds it.
+    pub fn release(&self, selection: Atom) {
+        if self.owns(selection) {
+            xcb::set_selection_owner(
+                &self.context.connection,
+                xcb::NONE,
+                selection,
+                xcb::CURRENT_TIME,
+            );
+            self.context.connection.flush();
+        }
+        self.forget(selection);
+    }
+
+    /// Clears `selection`, no matter which client owns it.
+    pub fn clear(&self, selection: Atom) {
+        self.forget(selection);
+        xcb::set_selection_owner(
+            &self.context.connection,
+            xcb::NONE,
+            selection,
+            xcb::CURRENT_TIME,
+        );
+        self.context.connection.flush();
+    }
+
+    fn forget(&self, selection: Atom) {
+        if let Ok(mut offers) = self.offers.lock() {
+            offers.remove(&selection);
+        }
+    }
+}
+
+impl Drop for SelectionServer {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+
+        // wake the serving thread up so that it notices the shutdown
+        let wakeup = xcb::ClientMessageEvent::new(
+            32,
+            self.context.window,
+            xcb::ATOM_NONE,
+            xcb::ClientMessageData::from_data32([0; 5]),
+        );
+        xcb::send_event(
+            &self.context.connection,
+            false,
+            self.context.window,
+            xcb::EVENT_MASK_NO_EVENT,
+            &wakeup,
+        );
+        self.context.connection.flush();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run(context: &Context, offers: &OfferMap, shutdown: &AtomicBool) {
+    // Data that doesn't fit in a single core protocol request is sent in
+    // chunks of that size through INCR. The limit raised by BIG-REQUESTS
+    // is not used, as requestors can't be expected to handle properties
+    // that large.
+    let chunk_size = context.connection.get_setup().maximum_request_length() as usize * 4 - 24;
+    let mut transfers = HashMap::new();
+
+    while let Some(event) = next_event(context, &mut transfers) {
+        if shutdown.load(Ordering::SeqCst) {
+            return;
+        }
+
+        match event.response_type() & !0x80 {
+            xcb::SELECTION_REQUEST => {
+                let event = unsafe { xcb::cast_event::<xcb::SelectionRequestEvent>(&event) };
+                // obsolete clients leave the property unset and expect
+                // the target to be used instead
+                let property = if event.property() == xcb::ATOM_NONE {
+                    event.target()
+                } else {
+                    event.property()
+                };
+
+                let answered =
+                    answer_request(context, offers, event, property, chunk_size, &mut transfers);
+
+                xcb::send_event(
+                    &context.connection,
+                    false,
+                    event.requestor(),
+                    xcb::EVENT_MASK_NO_EVENT,
+                    &xcb::SelectionNotifyEvent::new(
+                        event.time(),
+                        event.requestor(),
+                        event.selection(),
+                        event.target(),
+                        if answered { property } else { xcb::ATOM_NONE },
+                    ),
+                );
+                context.connection.flush();
+            }
+            xcb::PROPERTY_NOTIFY => {
+                let event = unsafe { xcb::cast_event::<xcb::PropertyNotifyEvent>(&event) };
+                if event.state() != xcb::PROPERTY_DELETE as u8 {
+                    continue;
+                }
+
+                let key = (event.window(), event.atom());
+                let done = match transfers.get_mut(&key) {
+                    Some(transfer) => {
+                        transfer.deadline = Instant::now() + TRANSFER_TIMEOUT;
+                        send_chunk(context, key, transfer, chunk_size)
+                    }
+                    None => continue,
+                };
+                if done {
+                    transfers.remove(&key);
+                }
+                context.connection.flush();
+            }
+            xcb::DESTROY_NOTIFY => {
+                let event = unsafe { xcb::cast_event::<xcb::DestroyNotifyEvent>(&event) };
+                transfers.retain(|&(requestor, _), _| requestor != event.window());
+            }
+            xcb::SELECTION_CLEAR => {
+                let event = unsafe { xcb::cast_event::<xcb::SelectionClearEvent>(&event) };
+                if let Ok(mut offers) = offers.lock() {
+                    offers.remove(&event.selection());
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Waits for the next event, abandoning INCR transfers whose requestor
+/// stopped deleting the property, so that their data is dropped.
+fn next_event(
+    context: &Context,
+    transfers: &mut HashMap<(Window, Atom), Transfer>,
+) -> Option<xcb::GenericEvent> {
+    loop {
+        if let Some(event) = context.connection.poll_for_event() {
+            return Some(event);
+        }
+        context.connection.has_error().ok()?;
+
+        let now = Instant::now();
+        transfers.retain(|_, transfer| transfer.deadline > now);
+        let deadline = match transfers.values().map(|transfer| transfer.deadline).min() {
+            Some(deadline) => deadline,
+            None => return context.connection.wait_for_event(),
+        };
+
+        let timeout = deadline.saturating_duration_since(now).as_millis() + 1;
+        let mut fd = libc::pollfd {
+            fd: context.connection.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        unsafe { libc::poll(&mut fd, 1, cmp::min(timeout, i32::MAX as u128) as i32) };
+    }
+}
+
+/// Writes the requested target to the requestor's property, returning
+/// false if the target is not offered.
+fn answer_request(
+    context: &Context,
+    offers: &OfferMap,
+    event: &xcb::SelectionRequestEvent,
+    property: Atom,
+    chunk_size: usize,
+    transfers: &mut HashMap<(Window, Atom), Transfer>,
+) -> bool {
+    let offers = match offers.lock() {
+        Ok(offers) => offers,
+        Err(_) => return false,
+    };
+    let offer = match offers.get(&event.selection()) {
+        Some(offer) => offer,
+        None => return false,
+    };
+
+    if event.target() == context.atoms.targets {
+        let targets: Vec<Atom> = Some(context.atoms.targets)
+            .into_iter()
+            .chain(offer.iter().map(|&(target, _)| target))
+            .collect();
+        xcb::change_property(
+            &context.connection,
+            xcb::PROP_MODE_REPLACE as u8,
+            event.requestor(),
+            property,
+            xcb::ATOM_ATOM,
+            32,
+            &targets,
+        );
+        return true;
+    }
+
+    let (target, data) = match offer.iter().find(|&&(target, _)| target == event.target()) {
+        Some(entry) => entry,
+        None => return false,
+    };
+
+    if data.len() <= chunk_size {
+        xcb::change_property(
+            &context.connection,
+            xcb::PROP_MODE_REPLACE as u8,
+            event.requestor(),
+            property,
+            *target,
+            8,
+            data.as_slice(),
+        );
+    } else {
+        // too large for a single request, hand the data over in chunks
+        // each time the requestor deletes the property, and give up if
+        // its window is destroyed
+        xcb::change_window_attributes(
+            &context.connection,
+            event.requestor(),
+            &[(
+                xcb::CW_EVENT_MASK,
+                xcb::EVENT_MASK_PROPERTY_CHANGE | xcb::EVENT_MASK_STRUCTURE_NOTIFY,
+            )],
+        );
+        xcb::change_property(
+            &context.connection,
+            xcb::PROP_MODE_REPLACE as u8,
+            event.requestor(),
+            property,
+            context.atoms.incr,
+            32,
+            &[data.len() as u32],
+        );
+        transfers.insert(
+            (event.requestor(), property),
+            Transfer {
+                target: *target,
+                data: Arc::clone(data),
+                pos: 0,
+                deadline: Instant::now() + TRANSFER_TIMEOUT,
+            },
+        );
+    }
+    true
+}
+
+/// Sends the next INCR chunk, returning true once the terminating empty
+/// chunk has been sent.
+fn send_chunk(
+    context: &Context,
+    (requestor, property): (Window, Atom),
+    transfer: &mut Transfer,
+    chunk_size: usize,
+) -> bool {
+    let len = cmp::min(chunk_size, transfer.data.len() - transfer.pos);
+    xcb::change_property(
+        &context.connection,
+        xcb::PROP_MODE_REPLACE as u8,
+        requestor,
+        property,
+        transfer.target,
+        8,
+        &transfer.data[transfer.pos..][..len],
+    );
+    transfer.pos += len;
+    len == 0
+}